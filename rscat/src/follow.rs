@@ -0,0 +1,290 @@
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use winux_cli::error::{Error, Status};
+use winux_cli::output::Output;
+use crate::input::{file_id, FileId};
+use crate::{copy, Mode};
+
+/// How long to wait between checks without inotify. With it, the longest
+/// wait before checking anyway, e.g. for a file that has yet to appear in
+/// a directory that does not exist yet.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Follow {
+    /// `-f`: keep reading the file that was opened, even once renamed.
+    Descriptor,
+    /// `-F`: keep reading whatever file has the name, across rotations.
+    Name,
+}
+
+/// A file being followed and how far it was printed.
+pub struct Followed {
+    path: PathBuf,
+    file: Option<File>,
+    position: u64,
+    id: Option<FileId>,
+    /// Whether the name is known to be gone, to only warn once
+    missing: bool,
+}
+
+impl Followed {
+    /// Follows `path` from where `file` stopped being read, or from the
+    /// current end of the file when that is not known.
+    pub fn new(path: PathBuf, file: Option<File>) -> Self {
+        let mut followed = Followed {
+            path,
+            file: None,
+            position: 0,
+            id: None,
+            missing: false,
+        };
+        let file = match file {
+            Some(file) => Some(file),
+            None => File::open(&followed.path).ok().and_then(|mut file| {
+                file.seek(SeekFrom::End(0)).ok()?;
+                Some(file)
+            }),
+        };
+        match file {
+            Some(mut file) => {
+                followed.position = file.stream_position().unwrap_or(0);
+                followed.id = file.metadata().ok().and_then(|metadata| file_id(&metadata));
+                followed.file = Some(file);
+            }
+            None => followed.missing = true,
+        }
+        followed
+    }
+
+    /// Prints what was appended since the last call, starting over if the
+    /// file was truncated.
+    fn read_new(&mut self, header: &mut Header, mode: &mut Mode, out: &mut Output) -> io::Result<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+
+        let len = file.metadata()?.len();
+        if len < self.position {
+            eprintln!("[WARNING] {}: file truncated", self.path.display());
+            self.position = file.seek(SeekFrom::Start(0))?;
+        }
+        if len == self.position {
+            return Ok(());
+        }
+
+        header.print(&self.path, out);
+        copy(file, mode, out)?;
+        self.position = file.stream_position()?;
+        Ok(())
+    }
+
+    /// Reopens the name when it now points to another file. Returns whether
+    /// it did, the new file then being read from its start.
+    fn reopen_if_replaced(&mut self, header: &mut Header, mode: &mut Mode, out: &mut Output) -> io::Result<bool> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                // Keep reading the old file, writers may not have moved on yet
+                if !self.missing {
+                    eprintln!("[WARNING] {}: has become inaccessible: {err}", self.path.display());
+                    self.missing = true;
+                }
+                return Ok(false);
+            }
+        };
+        let id = file_id(&metadata);
+        if self.file.is_some() && id == self.id {
+            return Ok(false);
+        }
+        let Ok(file) = File::open(&self.path) else {
+            return Ok(false);
+        };
+
+        // What was written to the old file before the switch comes first
+        self.read_new(header, mode, out)?;
+        if self.file.is_some() {
+            eprintln!("[WARNING] {}: has been replaced, following the new file", self.path.display());
+        } else {
+            eprintln!("[WARNING] {}: has appeared, following the new file", self.path.display());
+        }
+        self.file = Some(file);
+        self.position = 0;
+        self.id = id;
+        self.missing = false;
+        Ok(true)
+    }
+}
+
+/// Prints `==> name <==` before data from another file than the previous
+/// one, when several files are followed.
+pub struct Header {
+    enabled: bool,
+    last: Option<PathBuf>,
+}
+
+impl Header {
+    /// `last` is the file printed just before following starts.
+    pub fn new(enabled: bool, last: Option<PathBuf>) -> Self {
+        Header { enabled, last }
+    }
+
+    fn print(&mut self, path: &Path, out: &mut Output) {
+        if !self.enabled || self.last.as_deref() == Some(path) {
+            return;
+        }
+        if self.last.is_some() {
+            out.write_bytes(b"\n");
+        }
+        out.write_bytes(b"==> ");
+        out.write_bytes(path.as_os_str().as_encoded_bytes());
+        out.write_bytes(b" <==\n");
+        self.last = Some(path.to_path_buf());
+    }
+}
+
+/// Prints data appended to `files` as it comes. Only returns when there is
+/// nothing left to follow, which only happens when following names if
+/// there was nothing to start with.
+pub fn follow(how: Follow, mut files: Vec<Followed>, header: &mut Header, mode: &mut Mode, out: &mut Output, status: &mut Status) {
+    let mut watcher = Watcher::new();
+    for followed in &files {
+        watcher.watch(&followed.path, how);
+    }
+
+    loop {
+        for followed in &mut files {
+            let result = match how {
+                Follow::Name => followed.reopen_if_replaced(header, mode, out).map(|reopened| {
+                    if reopened {
+                        watcher.watch(&followed.path, how);
+                    }
+                }),
+                Follow::Descriptor => Ok(()),
+            };
+            let result = result.and_then(|()| followed.read_new(header, mode, out));
+            if let Err(err) = result {
+                status.report(Error::Io {
+                    path: followed.path.clone(),
+                    source: err,
+                });
+                followed.file = None;
+                followed.id = None;
+            }
+        }
+        out.flush();
+
+        if how == Follow::Descriptor {
+            files.retain(|followed| followed.file.is_some());
+        }
+        if files.is_empty() {
+            return;
+        }
+        watcher.wait();
+    }
+}
+
+/// Waits for followed files to change, with inotify on Linux and by polling
+/// elsewhere or when inotify cannot be used.
+struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: Option<inotify::Inotify>,
+}
+
+impl Watcher {
+    fn new() -> Self {
+        Watcher {
+            #[cfg(target_os = "linux")]
+            inotify: inotify::Inotify::new().ok(),
+        }
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn watch(&mut self, path: &Path, how: Follow) {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &self.inotify {
+            inotify.watch_file(path);
+            // To see the file come back after a rotation
+            if how == Follow::Name {
+                let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+                inotify.watch_dir(parent.unwrap_or(Path::new(".")));
+            }
+        }
+    }
+
+    fn wait(&self) {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &self.inotify {
+            inotify.wait(POLL_INTERVAL);
+            return;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    const FILE_EVENTS: u32 =
+        libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_CLOSE_WRITE | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF;
+    const DIR_EVENTS: u32 = libc::IN_CREATE | libc::IN_MOVED_TO;
+
+    pub struct Inotify {
+        fd: OwnedFd,
+    }
+
+    impl Inotify {
+        pub fn new() -> io::Result<Self> {
+            // SAFETY: no pointers involved, the descriptor is owned from here
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Inotify {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            })
+        }
+
+        pub fn watch_file(&self, path: &Path) {
+            self.add_watch(path, FILE_EVENTS);
+        }
+
+        pub fn watch_dir(&self, path: &Path) {
+            self.add_watch(path, DIR_EVENTS);
+        }
+
+        /// Failing to watch is fine, the periodic check still catches changes.
+        fn add_watch(&self, path: &Path, mask: u32) {
+            let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+                return;
+            };
+            // SAFETY: `path` is a valid C string for the duration of the call
+            unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) };
+        }
+
+        /// Blocks until some event arrives or `timeout` passes. The events
+        /// are only drained, every followed file is checked after a wake up.
+        pub fn wait(&self, timeout: Duration) {
+            let mut poll = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `poll` and `buffer` outlive the calls using them
+            unsafe {
+                libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int);
+                let mut buffer = [0u8; 4096];
+                while libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) > 0 {}
+            }
+        }
+    }
+}
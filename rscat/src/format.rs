@@ -0,0 +1,280 @@
+use winux_cli::output::Output;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Numbering {
+    #[default]
+    None,
+    /// `-n`: every output line.
+    All,
+    /// `-b`: only lines that are not empty.
+    NonBlank,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatOptions {
+    pub numbering: Numbering,
+    /// `-s`
+    pub squeeze_blank: bool,
+    /// `-E`: `$` at the end of each line.
+    pub show_ends: bool,
+    /// `-T`: tabs as `^I`.
+    pub show_tabs: bool,
+    /// `-v`: control characters as `^X` and high bytes as `M-X`.
+    pub show_nonprinting: bool,
+}
+
+/// Rewrites the byte stream line by line the way GNU cat does for its
+/// formatting flags. The state lives across inputs, so numbering continues
+/// from one file to the next and a last line without newline is completed
+/// by the next file.
+pub struct Formatter {
+    options: FormatOptions,
+    line_number: u64,
+    at_line_start: bool,
+    /// Empty lines seen in a row, to squeeze them with `-s`
+    blank_run: u64,
+    /// A `\r` at the end of a chunk, held back until we know whether it is
+    /// part of a `\r\n` that `-E` shows as `^M$`
+    pending_cr: bool,
+    rendered: Vec<u8>,
+}
+
+impl Formatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Formatter {
+            options,
+            line_number: 0,
+            at_line_start: true,
+            blank_run: 0,
+            pending_cr: false,
+            rendered: vec![],
+        }
+    }
+
+    /// Whether the formatter would pass every byte through unchanged.
+    pub fn is_identity(&self) -> bool {
+        let o = &self.options;
+        o.numbering == Numbering::None && !o.squeeze_blank && !o.show_ends && !o.show_tabs && !o.show_nonprinting
+    }
+
+    pub fn write(&mut self, chunk: &[u8], out: &mut Output) {
+        let mut pos = 0;
+        while pos < chunk.len() {
+            let newline = chunk[pos..].iter().position(|&b| b == b'\n').map(|i| pos + i);
+
+            if self.at_line_start && newline == Some(pos) {
+                self.blank_run += 1;
+                if !(self.options.squeeze_blank && self.blank_run > 1) {
+                    if self.options.numbering == Numbering::All {
+                        self.write_line_number(out);
+                    }
+                    self.write_line_end(out);
+                }
+                pos += 1;
+                continue;
+            }
+
+            if self.at_line_start {
+                self.blank_run = 0;
+                if self.options.numbering != Numbering::None {
+                    self.write_line_number(out);
+                }
+                self.at_line_start = false;
+            }
+
+            let end = newline.unwrap_or(chunk.len());
+            self.write_text(&chunk[pos..end], newline.is_some(), out);
+            if newline.is_some() {
+                self.write_line_end(out);
+                self.at_line_start = true;
+                pos = end + 1;
+            } else {
+                pos = end;
+            }
+        }
+    }
+
+    /// Writes out anything still held back once all inputs are done.
+    pub fn finish(&mut self, out: &mut Output) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            out.write_bytes(b"\r");
+        }
+    }
+
+    fn write_line_number(&mut self, out: &mut Output) {
+        self.line_number += 1;
+        write!(out, "{:>6}\t", self.line_number);
+    }
+
+    fn write_line_end(&mut self, out: &mut Output) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            out.write_bytes(b"^M");
+        }
+        if self.options.show_ends {
+            out.write_bytes(b"$");
+        }
+        out.write_bytes(b"\n");
+    }
+
+    /// Writes the part of a line between two newlines or chunk boundaries.
+    fn write_text(&mut self, text: &[u8], ends_line: bool, out: &mut Output) {
+        if text.is_empty() {
+            return;
+        }
+        self.finish(out);
+
+        let o = self.options;
+        let mut text = text;
+        // GNU cat shows the `\r` of a `\r\n` as `^M` with -E even without -v
+        if o.show_ends && !o.show_nonprinting && text.last() == Some(&b'\r') {
+            text = &text[..text.len() - 1];
+            self.pending_cr = true;
+        }
+
+        if !o.show_tabs && !o.show_nonprinting {
+            out.write_bytes(text);
+        } else {
+            self.rendered.clear();
+            for &byte in text {
+                render_byte(byte, o, &mut self.rendered);
+            }
+            out.write_bytes(&self.rendered);
+        }
+
+        if self.pending_cr && ends_line {
+            self.pending_cr = false;
+            out.write_bytes(b"^M");
+        }
+    }
+}
+
+/// Appends `byte` in GNU cat's `-v`/`-T` notation.
+fn render_byte(byte: u8, options: FormatOptions, rendered: &mut Vec<u8>) {
+    if byte == b'\t' {
+        if options.show_tabs {
+            rendered.extend_from_slice(b"^I");
+        } else {
+            rendered.push(byte);
+        }
+        return;
+    }
+    if !options.show_nonprinting {
+        rendered.push(byte);
+        return;
+    }
+
+    let mut byte = byte;
+    if byte >= 128 {
+        rendered.extend_from_slice(b"M-");
+        byte -= 128;
+    }
+    match byte {
+        0..=31 => rendered.extend_from_slice(&[b'^', byte + 64]),
+        127 => rendered.extend_from_slice(b"^?"),
+        _ => rendered.push(byte),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cat(options: FormatOptions, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut out = Output::memory();
+        let mut formatter = Formatter::new(options);
+        for chunk in chunks {
+            formatter.write(chunk, &mut out);
+        }
+        formatter.finish(&mut out);
+        out.take_memory()
+    }
+
+    const SHOW_ALL: FormatOptions = FormatOptions {
+        numbering: Numbering::None,
+        squeeze_blank: false,
+        show_ends: true,
+        show_tabs: true,
+        show_nonprinting: true,
+    };
+
+    const SHOW_ENDS: FormatOptions = FormatOptions {
+        numbering: Numbering::None,
+        squeeze_blank: false,
+        show_ends: true,
+        show_tabs: false,
+        show_nonprinting: false,
+    };
+
+    #[test]
+    fn show_all() {
+        assert_eq!(cat(SHOW_ALL, &[b"a\tb\x01\x7f\xff\r\n"]), b"a^Ib^A^?M-^?^M$\n");
+    }
+
+    #[test]
+    fn show_nonprinting_keeps_tabs() {
+        let options = FormatOptions {
+            show_nonprinting: true,
+            ..Default::default()
+        };
+        assert_eq!(cat(options, &[b"\x80\xa0\t\n"]), b"M-^@M- \t\n");
+    }
+
+    #[test]
+    fn show_ends_crlf() {
+        assert_eq!(cat(SHOW_ENDS, &[b"a\r\n"]), b"a^M$\n");
+        // Only the `\r` of a `\r\n` is shown without -v
+        assert_eq!(cat(SHOW_ENDS, &[b"a\rb\n"]), b"a\rb$\n");
+        assert_eq!(cat(SHOW_ENDS, &[b"a\r"]), b"a\r");
+    }
+
+    #[test]
+    fn show_ends_crlf_across_chunks() {
+        let mut first = vec![b'x'; 64 * 1024 - 1];
+        first.push(b'\r');
+        let mut expected = vec![b'x'; 64 * 1024 - 1];
+        expected.extend_from_slice(b"^M$\n");
+        assert_eq!(cat(SHOW_ENDS, &[&first, b"\n"]), expected);
+
+        let mut expected = vec![b'x'; 64 * 1024 - 1];
+        expected.extend_from_slice(b"\ry$\n");
+        assert_eq!(cat(SHOW_ENDS, &[&first, b"y\n"]), expected);
+    }
+
+    #[test]
+    fn number_all() {
+        let options = FormatOptions {
+            numbering: Numbering::All,
+            ..Default::default()
+        };
+        assert_eq!(cat(options, &[b"a\n\nb"]), b"     1\ta\n     2\t\n     3\tb");
+        // A line split across inputs is numbered once
+        assert_eq!(cat(options, &[b"a", b"b\n"]), b"     1\tab\n");
+    }
+
+    #[test]
+    fn number_nonblank() {
+        let options = FormatOptions {
+            numbering: Numbering::NonBlank,
+            ..Default::default()
+        };
+        assert_eq!(cat(options, &[b"a\n\nb\n"]), b"     1\ta\n\n     2\tb\n");
+    }
+
+    #[test]
+    fn squeeze_blank() {
+        let options = FormatOptions {
+            squeeze_blank: true,
+            ..Default::default()
+        };
+        assert_eq!(cat(options, &[b"a\n\n\n", b"\nb\n"]), b"a\n\nb\n");
+
+        let options = FormatOptions {
+            numbering: Numbering::All,
+            squeeze_blank: true,
+            ..Default::default()
+        };
+        assert_eq!(cat(options, &[b"\n\n\n"]), b"     1\t\n");
+    }
+}
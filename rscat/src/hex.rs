@@ -0,0 +1,270 @@
+use std::io;
+use winux_cli::output::Output;
+
+pub const DEFAULT_COLS: usize = 16;
+pub const DEFAULT_GROUP: usize = 2;
+/// Same limit as xxd, which keeps rows readable.
+pub const MAX_COLS: usize = 256;
+
+const ZEROS: [u8; 4096] = [0; 4096];
+
+/// Prints the bytes in the format of `xxd`: the offset, the bytes in hex
+/// and their printable ASCII. Inputs are dumped as one stream, so offsets
+/// keep counting from one file to the next.
+pub struct HexDump {
+    cols: usize,
+    /// Bytes per space separated group, 0 for no separation.
+    group: usize,
+    /// Position in the stream of the next byte received
+    position: u64,
+    start: u64,
+    end: Option<u64>,
+    row: Vec<u8>,
+    row_offset: u64,
+    line: Vec<u8>,
+}
+
+impl HexDump {
+    /// Dumps `length` bytes, or up to the end, from offset `start`.
+    pub fn new(cols: usize, group: usize, start: u64, length: Option<u64>) -> Self {
+        HexDump {
+            cols,
+            group,
+            position: 0,
+            start,
+            end: length.map(|length| start.saturating_add(length)),
+            row: Vec::with_capacity(cols),
+            row_offset: start,
+            line: vec![],
+        }
+    }
+
+    /// Whether the rest of the stream would be ignored.
+    pub fn is_done(&self) -> bool {
+        self.end.is_some_and(|end| self.position >= end)
+    }
+
+    pub fn write(&mut self, chunk: &[u8], out: &mut Output) {
+        let chunk_start = self.position;
+        self.position += chunk.len() as u64;
+
+        let from = self.start.saturating_sub(chunk_start).min(chunk.len() as u64) as usize;
+        let to = match self.end {
+            Some(end) => end.saturating_sub(chunk_start).min(chunk.len() as u64) as usize,
+            None => chunk.len(),
+        };
+        if from >= to {
+            return;
+        }
+
+        let mut bytes = &chunk[from..to];
+        while !bytes.is_empty() {
+            let take = (self.cols - self.row.len()).min(bytes.len());
+            self.row.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if self.row.len() == self.cols {
+                self.write_row(out);
+            }
+        }
+    }
+
+    /// Prints the last, incomplete row.
+    pub fn finish(&mut self, out: &mut Output) {
+        if !self.row.is_empty() {
+            self.write_row(out);
+        }
+    }
+
+    fn write_row(&mut self, out: &mut Output) {
+        let groups = if self.group == 0 { 1 } else { self.cols.div_ceil(self.group) };
+        let hex_width = self.cols * 2 + groups;
+
+        self.line.clear();
+        self.line.extend_from_slice(format!("{:08x}: ", self.row_offset).as_bytes());
+        let hex_start = self.line.len();
+        for (i, byte) in self.row.iter().enumerate() {
+            self.line.extend_from_slice(format!("{byte:02x}").as_bytes());
+            if self.group != 0 && (i + 1) % self.group == 0 {
+                self.line.push(b' ');
+            }
+        }
+        self.line.resize(hex_start + hex_width, b' ');
+        self.line.push(b' ');
+        for &byte in &self.row {
+            self.line.push(if byte.is_ascii_graphic() || byte == b' ' { byte } else { b'.' });
+        }
+        self.line.push(b'\n');
+        out.write_bytes(&self.line);
+
+        self.row_offset += self.row.len() as u64;
+        self.row.clear();
+    }
+}
+
+/// Turns a dump back into binary, like `xxd -r`. Each line is an offset, a
+/// colon and hex bytes up to two spaces in a row, so the ASCII column is
+/// ignored. Lines without an offset continue where the previous one ended,
+/// which also reads plain hex.
+pub struct Undump {
+    /// Bytes written so far, where the next line without offset goes
+    position: u64,
+    line_number: u64,
+    partial: Vec<u8>,
+    bytes: Vec<u8>,
+}
+
+impl Undump {
+    pub fn new() -> Self {
+        Undump {
+            position: 0,
+            line_number: 0,
+            partial: vec![],
+            bytes: vec![],
+        }
+    }
+
+    pub fn write(&mut self, chunk: &[u8], out: &mut Output) -> io::Result<()> {
+        let mut chunk = chunk;
+        while let Some(newline) = chunk.iter().position(|&b| b == b'\n') {
+            self.partial.extend_from_slice(&chunk[..newline]);
+            chunk = &chunk[newline + 1..];
+            let line = std::mem::take(&mut self.partial);
+            self.write_line(&line, out)?;
+            self.partial = line;
+            self.partial.clear();
+        }
+        self.partial.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Handles a last line without newline.
+    pub fn finish(&mut self, out: &mut Output) -> io::Result<()> {
+        if self.partial.is_empty() {
+            return Ok(());
+        }
+        let line = std::mem::take(&mut self.partial);
+        self.write_line(&line, out)
+    }
+
+    fn write_line(&mut self, line: &[u8], out: &mut Output) -> io::Result<()> {
+        self.line_number += 1;
+        let line_error = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {msg}", self.line_number));
+
+        let (offset, hex) = match line.iter().position(|&b| b == b':') {
+            Some(colon) => {
+                let offset = std::str::from_utf8(&line[..colon])
+                    .ok()
+                    .and_then(|offset| u64::from_str_radix(offset.trim(), 16).ok())
+                    .ok_or_else(|| line_error("invalid offset".to_owned()))?;
+                (offset, &line[colon + 1..])
+            }
+            None => (self.position, line),
+        };
+        if offset < self.position {
+            return Err(line_error(format!("offset {offset:#x} goes back before {:#x}", self.position)));
+        }
+
+        self.bytes.clear();
+        let mut digits = hex.iter().skip_while(|&&b| b == b' ').peekable();
+        let mut high: Option<u8> = None;
+        while let Some(&b) = digits.next() {
+            if b == b' ' {
+                if digits.peek() == Some(&&b' ') {
+                    break;
+                }
+                continue;
+            }
+            let Some(digit) = (b as char).to_digit(16) else {
+                break;
+            };
+            match high.take() {
+                Some(high) => self.bytes.push(high << 4 | digit as u8),
+                None => high = Some(digit as u8),
+            }
+        }
+
+        // Gaps between rows are holes in the original, filled with zeros
+        let mut gap = offset - self.position;
+        while gap > 0 {
+            let len = gap.min(ZEROS.len() as u64) as usize;
+            out.write_bytes(&ZEROS[..len]);
+            gap -= len as u64;
+        }
+        out.write_bytes(&self.bytes);
+        self.position = offset + self.bytes.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &[u8] = b"Hello, world!\n\x00\x01\xff abcdefghijk";
+
+    fn dump(mut dump: HexDump, chunks: &[&[u8]]) -> String {
+        let mut out = Output::memory();
+        for chunk in chunks {
+            dump.write(chunk, &mut out);
+        }
+        dump.finish(&mut out);
+        String::from_utf8(out.take_memory()).unwrap()
+    }
+
+    fn undump(text: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Output::memory();
+        let mut undump = Undump::new();
+        undump.write(text, &mut out)?;
+        undump.finish(&mut out)?;
+        Ok(out.take_memory())
+    }
+
+    #[test]
+    fn like_xxd() {
+        let expected = "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 0001  Hello, world!...\n\
+                        00000010: ff20 6162 6364 6566 6768 696a 6b         . abcdefghijk\n";
+        assert_eq!(dump(HexDump::new(DEFAULT_COLS, DEFAULT_GROUP, 0, None), &[INPUT]), expected);
+        // Rows do not depend on how the input is split
+        let (first, second) = INPUT.split_at(5);
+        assert_eq!(dump(HexDump::new(DEFAULT_COLS, DEFAULT_GROUP, 0, None), &[first, second]), expected);
+    }
+
+    #[test]
+    fn cols_without_groups() {
+        let text = dump(HexDump::new(4, 0, 0, None), &[INPUT]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "00000000: 48656c6c  Hell");
+        assert_eq!(lines[7], "0000001c: 6b        k");
+    }
+
+    #[test]
+    fn offset_and_length() {
+        let expected = "00000003: 6c 6f 2c 20 77                                   lo, w\n";
+        assert_eq!(dump(HexDump::new(DEFAULT_COLS, 1, 3, Some(5)), &[&INPUT[..4], &INPUT[4..]]), expected);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(dump(HexDump::new(DEFAULT_COLS, DEFAULT_GROUP, 0, None), &[]), "");
+    }
+
+    #[test]
+    fn round_trip() {
+        for (cols, group) in [(16, 2), (4, 0), (7, 3), (1, 1)] {
+            let text = dump(HexDump::new(cols, group, 0, None), &[INPUT]);
+            assert_eq!(undump(text.as_bytes()).unwrap(), INPUT, "cols {cols}, group {group}");
+        }
+    }
+
+    #[test]
+    fn plain_hex_and_gaps() {
+        assert_eq!(undump(b"4865\n6c6c6f").unwrap(), b"Hello");
+        assert_eq!(undump(b"00000002: 41  ..\n").unwrap(), b"\0\0A");
+    }
+
+    #[test]
+    fn offset_going_back() {
+        let err = undump(b"00000004: 4142\n00000002: 43\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: offset 0x2 goes back before 0x6");
+    }
+}
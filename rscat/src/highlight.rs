@@ -0,0 +1,311 @@
+use std::path::Path;
+use ansi_term::{Colour, Style};
+
+/// What is needed to colour a language well enough for reading: its
+/// keywords, comments and string quotes. This is a lexer for the common
+/// cases, not a parser, so it can be fooled by unusual code.
+pub struct Syntax {
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "class", "const", "continue", "default", "delete", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "inline", "int", "long", "namespace", "new", "nullptr", "private",
+    "protected", "public", "register", "return", "short", "signed", "sizeof", "static", "struct", "switch", "template",
+    "this", "typedef", "union", "unsigned", "using", "virtual", "void", "volatile", "while", "#include", "#define",
+    "#ifdef", "#ifndef", "#endif", "#if", "#else", "#pragma",
+];
+
+static SYNTAXES: &[Syntax] = &[
+    Syntax {
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+            "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+            "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Syntax {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        keywords: C_KEYWORDS,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["go"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "false", "for",
+            "func", "go", "goto", "if", "import", "interface", "map", "nil", "package", "range", "return", "select",
+            "struct", "switch", "true", "type", "var",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Syntax {
+        extensions: &["java", "kt", "cs"],
+        keywords: &[
+            "abstract", "boolean", "break", "case", "catch", "class", "const", "continue", "default", "do", "else",
+            "enum", "extends", "false", "final", "finally", "for", "fun", "if", "implements", "import", "interface",
+            "internal", "namespace", "new", "null", "override", "package", "private", "protected", "public", "return",
+            "static", "super", "switch", "this", "throw", "throws", "true", "try", "using", "val", "var", "void",
+            "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do",
+            "else", "export", "extends", "false", "finally", "for", "from", "function", "if", "import", "in",
+            "instanceof", "interface", "let", "new", "null", "of", "return", "switch", "this", "throw", "true", "try",
+            "type", "typeof", "undefined", "var", "void", "while", "yield",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Syntax {
+        extensions: &["py", "pyi"],
+        keywords: &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+            "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+            "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+            "return", "then", "until", "while",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["toml", "ini", "conf", "cfg"],
+        keywords: &["true", "false"],
+        line_comments: &["#", ";"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["yaml", "yml"],
+        keywords: &["true", "false", "null", "yes", "no"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        extensions: &["json"],
+        keywords: &["true", "false", "null"],
+        line_comments: &[],
+        block_comment: None,
+        quotes: &['"'],
+    },
+];
+
+impl Syntax {
+    /// The syntax for the extension of `path`, if it is a known one.
+    pub fn for_path(path: &Path) -> Option<&'static Syntax> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        SYNTAXES.iter().find(|syntax| syntax.extensions.contains(&extension.as_str()))
+    }
+}
+
+/// Colours one file line after line, remembering block comments that span
+/// several lines.
+pub struct Highlighter {
+    syntax: &'static Syntax,
+    in_block_comment: bool,
+}
+
+impl Highlighter {
+    pub fn new(syntax: &'static Syntax) -> Self {
+        Highlighter {
+            syntax,
+            in_block_comment: false,
+        }
+    }
+
+    /// Appends `line`, without its line ending, with escape codes to `out`.
+    pub fn highlight(&mut self, line: &str, out: &mut String) {
+        let comment = Colour::Fixed(244).normal();
+        let string = Colour::Green.normal();
+        let number = Colour::Purple.normal();
+        let keyword = Colour::Red.normal();
+        let type_name = Colour::Cyan.normal();
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            if self.in_block_comment {
+                let (_, end) = self.syntax.block_comment.expect("only set for syntaxes with block comments");
+                let len = match rest.find(end) {
+                    Some(i) => {
+                        self.in_block_comment = false;
+                        i + end.len()
+                    }
+                    None => rest.len(),
+                };
+                paint(comment, &rest[..len], out);
+                rest = &rest[len..];
+                continue;
+            }
+
+            if self.syntax.line_comments.iter().any(|start| rest.starts_with(start)) {
+                paint(comment, rest, out);
+                break;
+            }
+            if let Some((start, _)) = self.syntax.block_comment {
+                if rest.starts_with(start) {
+                    self.in_block_comment = true;
+                    paint(comment, start, out);
+                    rest = &rest[start.len()..];
+                    continue;
+                }
+            }
+
+            let first = rest.chars().next().expect("rest is not empty");
+            let len = if self.syntax.quotes.contains(&first) {
+                let len = string_len(rest, first);
+                paint(string, &rest[..len], out);
+                len
+            } else if first.is_ascii_digit() {
+                let len = word_len(rest, |c| c.is_ascii_alphanumeric() || c == '.' || c == '_');
+                paint(number, &rest[..len], out);
+                len
+            } else if first.is_alphabetic() || first == '_' || first == '#' {
+                let len = word_len(rest, |c| c.is_alphanumeric() || c == '_');
+                let word = &rest[..len];
+                if self.syntax.keywords.contains(&word) {
+                    paint(keyword, word, out);
+                } else if first.is_uppercase() {
+                    paint(type_name, word, out);
+                } else {
+                    out.push_str(word);
+                }
+                len
+            } else {
+                out.push(first);
+                first.len_utf8()
+            };
+            rest = &rest[len..];
+        }
+    }
+}
+
+fn paint(style: Style, text: &str, out: &mut String) {
+    out.push_str(&style.paint(text).to_string());
+}
+
+/// Length of the leading word, whose first character is always included.
+fn word_len(text: &str, in_word: impl Fn(char) -> bool) -> usize {
+    let first = text.chars().next().map_or(0, char::len_utf8);
+    first + text[first..].find(|c| !in_word(c)).unwrap_or(text.len() - first)
+}
+
+/// Length of the string literal at the start of `text`, up to the closing
+/// quote or the end of the line.
+fn string_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return i + c.len_utf8(),
+            _ => (),
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax(extension: &str) -> &'static Syntax {
+        Syntax::for_path(Path::new(&format!("file.{extension}"))).unwrap()
+    }
+
+    fn highlight(highlighter: &mut Highlighter, line: &str) -> String {
+        let mut out = String::new();
+        highlighter.highlight(line, &mut out);
+        out
+    }
+
+    fn painted(parts: &[(Option<Colour>, &str)]) -> String {
+        parts
+            .iter()
+            .map(|&(colour, text)| match colour {
+                Some(colour) => colour.normal().paint(text).to_string(),
+                None => text.to_owned(),
+            })
+            .collect()
+    }
+
+    const COMMENT: Option<Colour> = Some(Colour::Fixed(244));
+    const STRING: Option<Colour> = Some(Colour::Green);
+    const KEYWORD: Option<Colour> = Some(Colour::Red);
+    const TYPE: Option<Colour> = Some(Colour::Cyan);
+
+    #[test]
+    fn known_extensions() {
+        assert!(Syntax::for_path(Path::new("main.RS")).is_some());
+        assert!(Syntax::for_path(Path::new("notes.txt")).is_none());
+        assert!(Syntax::for_path(Path::new("Makefile")).is_none());
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let mut highlighter = Highlighter::new(syntax("rs"));
+        let line = highlight(&mut highlighter, r#"let s = "abc"#);
+        assert_eq!(line, painted(&[(KEYWORD, "let"), (None, " s = "), (STRING, r#""abc"#)]));
+    }
+
+    #[test]
+    fn escaped_quote() {
+        let mut highlighter = Highlighter::new(syntax("rs"));
+        let line = highlight(&mut highlighter, r#""a\"b" x"#);
+        assert_eq!(line, painted(&[(STRING, r#""a\"b""#), (None, " x")]));
+        assert_eq!(string_len(r#""a\\" x"#, '"'), 5);
+    }
+
+    #[test]
+    fn block_comment_across_lines() {
+        let mut highlighter = Highlighter::new(syntax("c"));
+        assert_eq!(highlight(&mut highlighter, "a /* b"), painted(&[(None, "a "), (COMMENT, "/*"), (COMMENT, " b")]));
+        assert_eq!(highlight(&mut highlighter, "still"), painted(&[(COMMENT, "still")]));
+        assert_eq!(highlight(&mut highlighter, "c */ d"), painted(&[(COMMENT, "c */"), (None, " d")]));
+    }
+
+    #[test]
+    fn line_comment() {
+        let mut highlighter = Highlighter::new(syntax("py"));
+        let line = highlight(&mut highlighter, "x # é \"");
+        assert_eq!(line, painted(&[(None, "x "), (COMMENT, "# é \"")]));
+    }
+
+    #[test]
+    fn multibyte_identifiers() {
+        let mut highlighter = Highlighter::new(syntax("py"));
+        let line = highlight(&mut highlighter, "Été=ñandú·'ü'");
+        assert_eq!(line, painted(&[(TYPE, "Été"), (None, "=ñandú·"), (STRING, "'ü'")]));
+        assert_eq!(word_len("ñandú·x", |c| c.is_alphanumeric()), "ñandú".len());
+        // The first character is taken even when it is not part of a word
+        assert_eq!(word_len("· x", |c| c.is_alphanumeric()), "·".len());
+    }
+}
@@ -0,0 +1,191 @@
+use std::ffi::OsStr;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, StdinLock};
+#[cfg(target_os = "linux")]
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+/// The operand standing for standard input.
+pub const STDIN: &str = "-";
+
+/// One operand of rscat, read in the order given on the command line.
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+/// Enough bytes to tell every supported format apart.
+const MAGIC_LEN: usize = 6;
+
+/// An opened input.
+pub enum Reader {
+    Stdin(StdinLock<'static>),
+    File(File),
+    /// Bytes that went through a decoder, or were peeked at to find one.
+    Decoded(Box<dyn Read>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// The format `magic` starts with, going by the content rather than the
+    /// file extension so rotated logs like `app.log.1` are handled too.
+    fn detect(magic: &[u8]) -> Option<Compression> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if magic.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Identifies a file independently of its name, to notice rotations or
+/// a file being both read and written.
+pub type FileId = (u64, u64);
+
+#[cfg(unix)]
+pub fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without a stable file id, rotations show as truncations or missing files.
+#[cfg(not(unix))]
+pub fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// The regular file standard output is redirected to, if any.
+#[cfg(unix)]
+pub fn output_id() -> Option<FileId> {
+    use std::os::fd::AsFd;
+    let stdout = File::from(io::stdout().as_fd().try_clone_to_owned().ok()?);
+    let metadata = stdout.metadata().ok()?;
+    if metadata.is_file() {
+        file_id(&metadata)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+pub fn output_id() -> Option<FileId> {
+    None
+}
+
+impl Input {
+    pub fn from_arg(arg: &OsStr) -> Input {
+        if arg == STDIN {
+            Input::Stdin
+        } else {
+            Input::File(PathBuf::from(arg))
+        }
+    }
+
+    /// How the input is named in diagnostics.
+    pub fn path(&self) -> &Path {
+        match self {
+            Input::Stdin => Path::new(STDIN),
+            Input::File(path) => path,
+        }
+    }
+
+    /// Whether the input is the file output goes to, as with `rscat f >> f`,
+    /// which would otherwise read what it writes and never end.
+    pub fn is_output(&self, output: Option<FileId>) -> bool {
+        let Input::File(path) = self else {
+            return false;
+        };
+        output.is_some() && fs::metadata(path).ok().and_then(|metadata| file_id(&metadata)) == output
+    }
+
+    /// Opens the input, decompressing it when `decompress` is set and it
+    /// starts with a known magic number. Other inputs are read unchanged.
+    pub fn open(&self, decompress: bool) -> io::Result<Reader> {
+        let reader = match self {
+            Input::Stdin => Reader::Stdin(io::stdin().lock()),
+            Input::File(path) => Reader::File(File::open(path)?),
+        };
+        if decompress {
+            reader.decompressed()
+        } else {
+            Ok(reader)
+        }
+    }
+}
+
+impl Reader {
+    /// The file descriptor the bytes come from, for kernel side copies.
+    #[cfg(target_os = "linux")]
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        match self {
+            Reader::Stdin(stdin) => Some(stdin.as_fd()),
+            Reader::File(file) => Some(file.as_fd()),
+            Reader::Decoded(_) => None,
+        }
+    }
+
+    /// The file when the input is read directly from one.
+    pub fn into_file(self) -> Option<File> {
+        match self {
+            Reader::File(file) => Some(file),
+            Reader::Stdin(_) | Reader::Decoded(_) => None,
+        }
+    }
+
+    fn decompressed(mut self) -> io::Result<Reader> {
+        let mut magic = [0; MAGIC_LEN];
+        let mut len = 0;
+        while len < MAGIC_LEN {
+            match self.read(&mut magic[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        let compression = Compression::detect(&magic[..len]);
+
+        // Plain files go back to the start to keep the zero copy path
+        if let (None, Reader::File(file)) = (compression, &mut self) {
+            if file.seek(SeekFrom::Current(-(len as i64))).is_ok() {
+                return Ok(self);
+            }
+        }
+
+        let stream = Cursor::new(magic[..len].to_vec()).chain(self);
+        let decoded: Box<dyn Read> = match compression {
+            None => Box::new(stream),
+            Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(stream)),
+            Some(Compression::Zstd) => Box::new(zstd::Decoder::new(stream)?),
+            Some(Compression::Xz) => Box::new(XzDecoder::new_multi_decoder(stream)),
+            Some(Compression::Bzip2) => Box::new(MultiBzDecoder::new(stream)),
+        };
+        Ok(Reader::Decoded(decoded))
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Reader::Stdin(stdin) => stdin.read(buf),
+            Reader::File(file) => file.read(buf),
+            Reader::Decoded(reader) => reader.read(buf),
+        }
+    }
+}
@@ -1,52 +1,487 @@
-mod rscli;
+mod follow;
+mod format;
+mod hex;
+mod highlight;
+mod input;
+mod pretty;
+mod range;
+#[cfg(target_os = "linux")]
+mod zero_copy;
 
 use std::env;
-use std::fs::read_to_string;
-use rscli::Rscli;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use follow::{Follow, Followed, Header};
+use format::{FormatOptions, Formatter, Numbering};
+use hex::{HexDump, Undump, DEFAULT_COLS, DEFAULT_GROUP, MAX_COLS};
+use input::{Input, STDIN};
+use range::{parse_bounds, Range};
+use winux_cli::error::{Error, Status};
+use winux_cli::output::Output;
+use winux_cli::rscli::{Arity, Flag, Rscli, ValueKind};
 
-fn validate(drcli: &Rscli) -> bool {
-    if drcli.free_args.len() == 0 {
-        return false;
+const ABOUT: &str = "Concatenate files and print them on the standard output.";
+const SYNOPSIS: &str = "[OPTIONS] [FILE]...";
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const NUMBER_FLAG: &str = "n";
+const NUMBER_LONG: &str = "number";
+const NUMBER_DESC: &str = "Number all output lines";
+const NUMBER_NONBLANK_FLAG: &str = "b";
+const NUMBER_NONBLANK_LONG: &str = "number-nonblank";
+const NUMBER_NONBLANK_DESC: &str = "Number nonempty output lines, overrides -n";
+const SQUEEZE_BLANK_FLAG: &str = "s";
+const SQUEEZE_BLANK_LONG: &str = "squeeze-blank";
+const SQUEEZE_BLANK_DESC: &str = "Suppress repeated empty output lines";
+const SHOW_ALL_FLAG: &str = "A";
+const SHOW_ALL_LONG: &str = "show-all";
+const SHOW_ALL_DESC: &str = "Equivalent to -vET";
+const SHOW_NONPRINTING_FLAG: &str = "v";
+const SHOW_NONPRINTING_LONG: &str = "show-nonprinting";
+const SHOW_NONPRINTING_DESC: &str = "Use ^ and M- notation, except for LFD and TAB";
+const SHOW_ENDS_FLAG: &str = "E";
+const SHOW_ENDS_LONG: &str = "show-ends";
+const SHOW_ENDS_DESC: &str = "Display $ at end of each line";
+const SHOW_TABS_FLAG: &str = "T";
+const SHOW_TABS_LONG: &str = "show-tabs";
+const SHOW_TABS_DESC: &str = "Display TAB characters as ^I";
+const DECOMPRESS_FLAG: &str = "z";
+const DECOMPRESS_LONG: &str = "decompress";
+const DECOMPRESS_DESC: &str = "Decompress gzip, zstd, xz and bzip2 inputs, print others as is";
+const HEX_FLAG: &str = "x";
+const HEX_LONG: &str = "hex";
+const HEX_DESC: &str = "Print a hex and ASCII dump with offsets, like xxd";
+const HEX_COLS_FLAG: &str = "hex-cols";
+const HEX_COLS_DESC: &str = "Bytes per row of the hex dump (default: 16, max: 256)";
+const HEX_GROUP_FLAG: &str = "hex-group";
+const HEX_GROUP_DESC: &str = "Bytes per group of the hex dump, 0 for none (default: 2)";
+const OFFSET_FLAG: &str = "offset";
+const OFFSET_DESC: &str = "Start the hex dump at this byte offset";
+const LENGTH_FLAG: &str = "length";
+const LENGTH_DESC: &str = "Stop the hex dump after this many bytes";
+const REVERSE_FLAG: &str = "r";
+const REVERSE_LONG: &str = "reverse";
+const REVERSE_DESC: &str = "Turn a hex dump back into binary";
+const HEAD_FLAG: &str = "head";
+const HEAD_DESC: &str = "Print only the first NUM lines of each input";
+const TAIL_FLAG: &str = "tail";
+const TAIL_DESC: &str = "Print only the last NUM lines of each input";
+const LINES_FLAG: &str = "lines";
+const LINES_DESC: &str = "Print only lines A to B of each input, as A:B, A: or :B";
+const BYTES_FLAG: &str = "bytes";
+const BYTES_DESC: &str = "Print only bytes A to B of each input, as A:B, A: or :B";
+const FOLLOW_FLAG: &str = "f";
+const FOLLOW_LONG: &str = "follow";
+const FOLLOW_DESC: &str = "Keep printing data appended to the files";
+const FOLLOW_NAME_FLAG: &str = "F";
+const FOLLOW_NAME_LONG: &str = "follow-name";
+const FOLLOW_NAME_DESC: &str = "Like -f, but follow the file names across truncation and rotation";
+const PRETTY_FLAG: &str = "p";
+const PRETTY_LONG: &str = "pretty";
+const PRETTY_DESC: &str = "On a terminal, highlight syntax, number lines and page long output with $PAGER";
+
+/// What rscat does with the bytes it reads.
+enum Mode {
+    Cat(Formatter),
+    Hex(HexDump),
+    Reverse(Undump),
+}
+
+impl Mode {
+    /// Whether the bytes go out unchanged, so they can be copied kernel side.
+    fn is_identity(&self) -> bool {
+        matches!(self, Mode::Cat(formatter) if formatter.is_identity())
+    }
+
+    /// Whether the rest of the input can be skipped.
+    fn is_done(&self) -> bool {
+        matches!(self, Mode::Hex(dump) if dump.is_done())
     }
 
-    return true;
+    fn write(&mut self, chunk: &[u8], out: &mut Output) -> io::Result<()> {
+        match self {
+            Mode::Cat(formatter) if formatter.is_identity() => out.write_bytes(chunk),
+            Mode::Cat(formatter) => formatter.write(chunk, out),
+            Mode::Hex(dump) => dump.write(chunk, out),
+            Mode::Reverse(undump) => return undump.write(chunk, out),
+        }
+        Ok(())
+    }
+
+    /// Called once all inputs are read.
+    fn finish(&mut self, out: &mut Output) -> io::Result<()> {
+        match self {
+            Mode::Cat(formatter) => formatter.finish(out),
+            Mode::Hex(dump) => dump.finish(out),
+            Mode::Reverse(undump) => return undump.finish(out),
+        }
+        Ok(())
+    }
 }
 
-fn read_file(file_path: &String) -> Option<String> {
-    match read_to_string(file_path) {
-        Ok(content) => {
-            Some(content)
-        }, 
-        Err(err) => {
-            eprintln!("{err}");
-            None
+/// Prints `input`. Plain files are handed back once read, positioned at the
+/// end, for follow mode to pick up from there.
+fn cat_input(input: &Input, decompress: bool, range: Option<Range>, mode: &mut Mode, out: &mut Output) -> Result<Option<File>, Error> {
+    let io_error = |err| Error::Io {
+        path: input.path().to_path_buf(),
+        source: err,
+    };
+    let mut reader = input.open(decompress).map_err(io_error)?;
+    if let Some(range) = range {
+        reader = range.apply(reader).map_err(io_error)?;
+    }
+
+    #[cfg(target_os = "linux")]
+    if mode.is_identity() {
+        if let Some(fd) = reader.fd() {
+            out.flush();
+            if zero_copy::copy(fd).map_err(io_error)? {
+                return Ok(reader.into_file());
+            }
+        }
+    }
+
+    copy(&mut reader, mode, out).map_err(io_error)?;
+    Ok(reader.into_file())
+}
+
+/// Streams `reader` to `out` in fixed size chunks, so big files are never
+/// held in memory. Without formatting flags binary files come out unchanged.
+fn copy(reader: &mut dyn Read, mode: &mut Mode, out: &mut Output) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    while !mode.is_done() {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                mode.write(&buffer[..n], out)?;
+                // Like cat, hand over what was read right away
+                out.flush();
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
         }
     }
+    Ok(())
+}
+
+/// The inputs in command line order, standard input when there are none.
+fn get_inputs(cli: &Rscli) -> Vec<Input> {
+    if cli.free_args.is_empty() {
+        vec![Input::from_arg(STDIN.as_ref())]
+    } else {
+        cli.free_args.iter().map(|arg| Input::from_arg(arg)).collect()
+    }
+}
+
+fn get_formatter(cli: &Rscli) -> Formatter {
+    let numbering = if cli.find_user_flag_by_id(NUMBER_NONBLANK_FLAG).is_some() {
+        Numbering::NonBlank
+    } else if cli.find_user_flag_by_id(NUMBER_FLAG).is_some() {
+        Numbering::All
+    } else {
+        Numbering::None
+    };
+    let show_all = cli.find_user_flag_by_id(SHOW_ALL_FLAG).is_some();
+    Formatter::new(FormatOptions {
+        numbering,
+        squeeze_blank: cli.find_user_flag_by_id(SQUEEZE_BLANK_FLAG).is_some(),
+        show_ends: show_all || cli.find_user_flag_by_id(SHOW_ENDS_FLAG).is_some(),
+        show_tabs: show_all || cli.find_user_flag_by_id(SHOW_TABS_FLAG).is_some(),
+        show_nonprinting: show_all || cli.find_user_flag_by_id(SHOW_NONPRINTING_FLAG).is_some(),
+    })
+}
+
+fn get_range(cli: &Rscli) -> Option<Range> {
+    let given: Vec<&str> = [HEAD_FLAG, TAIL_FLAG, LINES_FLAG, BYTES_FLAG]
+        .into_iter()
+        .filter(|id| cli.find_user_flag_by_id(id).is_some())
+        .collect();
+    if given.len() > 1 {
+        cli.fail(Error::Usage(format!("--{} and --{} cannot be used together", given[0], given[1])));
+    }
+
+    let bounds = |id| match parse_bounds(cli.get_str(id)?) {
+        Ok(bounds) => Some(bounds),
+        Err(msg) => cli.fail(Error::Usage(format!("--{id}: {msg}"))),
+    };
+    if let Some(count) = cli.get_usize(HEAD_FLAG) {
+        Some(Range::Lines { first: 1, last: Some(count as u64) })
+    } else if let Some(count) = cli.get_usize(TAIL_FLAG) {
+        Some(Range::Tail(count as u64))
+    } else if let Some((first, last)) = bounds(LINES_FLAG) {
+        Some(Range::Lines { first, last })
+    } else {
+        bounds(BYTES_FLAG).map(|(first, last)| Range::Bytes { first, last })
+    }
+}
+
+fn get_follow(cli: &Rscli) -> Option<Follow> {
+    if cli.find_user_flag_by_id(FOLLOW_NAME_FLAG).is_some() {
+        Some(Follow::Name)
+    } else if cli.find_user_flag_by_id(FOLLOW_FLAG).is_some() {
+        Some(Follow::Descriptor)
+    } else {
+        None
+    }
+}
+
+fn get_hex_dump(cli: &Rscli) -> HexDump {
+    let cols = cli.get_usize(HEX_COLS_FLAG).unwrap_or(DEFAULT_COLS);
+    if cols == 0 || cols > MAX_COLS {
+        cli.fail(Error::Usage(format!("--{HEX_COLS_FLAG} must be between 1 and {MAX_COLS}")));
+    }
+    let group = cli.get_usize(HEX_GROUP_FLAG).unwrap_or(DEFAULT_GROUP);
+    let offset = cli.get_size(OFFSET_FLAG).unwrap_or(0);
+    HexDump::new(cols, group, offset, cli.get_size(LENGTH_FLAG))
 }
 
-fn start(cli: &Rscli) {
-    for file in cli.free_args.iter() {
-        if let Some(content) = read_file(file) {
-            println!("{content}");
+fn get_mode(cli: &Rscli) -> Mode {
+    if cli.find_user_flag_by_id(REVERSE_FLAG).is_some() {
+        Mode::Reverse(Undump::new())
+    } else if cli.find_user_flag_by_id(HEX_FLAG).is_some() {
+        Mode::Hex(get_hex_dump(cli))
+    } else {
+        Mode::Cat(get_formatter(cli))
+    }
+}
+
+fn start(cli: &Rscli, out: &mut Output) -> Status {
+    let decompress = cli.find_user_flag_by_id(DECOMPRESS_FLAG).is_some();
+    let range = get_range(cli);
+    let inputs = get_inputs(cli);
+    // Piped output stays plain bytes, for other tools to read
+    if cli.find_user_flag_by_id(PRETTY_FLAG).is_some() && out.is_terminal() {
+        return pretty::print(&inputs, decompress, range, out);
+    }
+
+    let mut status = Status::new();
+    let mut mode = get_mode(cli);
+    let follow = get_follow(cli);
+    let mut followed = vec![];
+    let output = input::output_id();
+    for input in &inputs {
+        if input.is_output(output) {
+            status.report(Error::Io {
+                path: input.path().to_path_buf(),
+                source: io::Error::other("input file is output file"),
+            });
+            continue;
+        }
+        let file = match cat_input(input, decompress, range, &mut mode, out) {
+            Ok(file) => file,
+            Err(err) => {
+                status.report(err);
+                None
+            }
+        };
+        // Standard input is only read to its end, like tail does for a pipe
+        match (follow, input) {
+            (Some(_), Input::File(path)) => followed.push(Followed::new(path.clone(), file)),
+            (Some(Follow::Name), Input::Stdin) => eprintln!("[WARNING] cannot follow '{STDIN}' by name"),
+            _ => (),
         }
     }
+    if let Err(err) = mode.finish(out) {
+        let path = inputs.last().map(|input| input.path().to_path_buf()).unwrap_or_default();
+        status.report(Error::Io { path, source: err });
+    }
+
+    if let Some(follow) = follow {
+        let last = inputs.last().map(|input| input.path().to_path_buf());
+        let mut header = Header::new(followed.len() > 1, last);
+        follow::follow(follow, followed, &mut header, &mut mode, out, &mut status);
+    }
+    status
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<OsString> = env::args_os().collect();
+
+    let program_name = args.first().expect("[ERROR] Program name should exist.").to_string_lossy().into_owned();
+    let number = Flag {
+        id: NUMBER_FLAG,
+        long: Some(NUMBER_LONG),
+        description: NUMBER_DESC,
+        ..Default::default()
+    };
+    let number_nonblank = Flag {
+        id: NUMBER_NONBLANK_FLAG,
+        long: Some(NUMBER_NONBLANK_LONG),
+        description: NUMBER_NONBLANK_DESC,
+        ..Default::default()
+    };
+    let squeeze_blank = Flag {
+        id: SQUEEZE_BLANK_FLAG,
+        long: Some(SQUEEZE_BLANK_LONG),
+        description: SQUEEZE_BLANK_DESC,
+        ..Default::default()
+    };
+    let show_all = Flag {
+        id: SHOW_ALL_FLAG,
+        long: Some(SHOW_ALL_LONG),
+        description: SHOW_ALL_DESC,
+        ..Default::default()
+    };
+    let show_nonprinting = Flag {
+        id: SHOW_NONPRINTING_FLAG,
+        long: Some(SHOW_NONPRINTING_LONG),
+        description: SHOW_NONPRINTING_DESC,
+        ..Default::default()
+    };
+    let show_ends = Flag {
+        id: SHOW_ENDS_FLAG,
+        long: Some(SHOW_ENDS_LONG),
+        description: SHOW_ENDS_DESC,
+        ..Default::default()
+    };
+    let show_tabs = Flag {
+        id: SHOW_TABS_FLAG,
+        long: Some(SHOW_TABS_LONG),
+        description: SHOW_TABS_DESC,
+        ..Default::default()
+    };
+    let decompress = Flag {
+        id: DECOMPRESS_FLAG,
+        long: Some(DECOMPRESS_LONG),
+        description: DECOMPRESS_DESC,
+        ..Default::default()
+    };
+    let hex = Flag {
+        id: HEX_FLAG,
+        long: Some(HEX_LONG),
+        description: HEX_DESC,
+        ..Default::default()
+    };
+    let hex_cols = Flag {
+        id: HEX_COLS_FLAG,
+        long: Some(HEX_COLS_FLAG),
+        description: HEX_COLS_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+    let hex_group = Flag {
+        id: HEX_GROUP_FLAG,
+        long: Some(HEX_GROUP_FLAG),
+        description: HEX_GROUP_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+    let offset = Flag {
+        id: OFFSET_FLAG,
+        long: Some(OFFSET_FLAG),
+        description: OFFSET_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Size,
+        ..Default::default()
+    };
+    let length = Flag {
+        id: LENGTH_FLAG,
+        long: Some(LENGTH_FLAG),
+        description: LENGTH_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Size,
+        ..Default::default()
+    };
+    let reverse = Flag {
+        id: REVERSE_FLAG,
+        long: Some(REVERSE_LONG),
+        description: REVERSE_DESC,
+        ..Default::default()
+    };
+    let head = Flag {
+        id: HEAD_FLAG,
+        long: Some(HEAD_FLAG),
+        description: HEAD_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+    let tail = Flag {
+        id: TAIL_FLAG,
+        long: Some(TAIL_FLAG),
+        description: TAIL_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+    let lines = Flag {
+        id: LINES_FLAG,
+        long: Some(LINES_FLAG),
+        description: LINES_DESC,
+        arity: Arity::One,
+        ..Default::default()
+    };
+    let bytes = Flag {
+        id: BYTES_FLAG,
+        long: Some(BYTES_FLAG),
+        description: BYTES_DESC,
+        arity: Arity::One,
+        ..Default::default()
+    };
+    let follow = Flag {
+        id: FOLLOW_FLAG,
+        long: Some(FOLLOW_LONG),
+        description: FOLLOW_DESC,
+        ..Default::default()
+    };
+    let follow_name = Flag {
+        id: FOLLOW_NAME_FLAG,
+        long: Some(FOLLOW_NAME_LONG),
+        description: FOLLOW_NAME_DESC,
+        ..Default::default()
+    };
+    let pretty = Flag {
+        id: PRETTY_FLAG,
+        long: Some(PRETTY_LONG),
+        description: PRETTY_DESC,
+        ..Default::default()
+    };
 
-    let program_name = args.get(0).expect("[ERROR] Program name should exist.").clone();
-    let program_flags = vec![];
+    let program_flags = vec![
+        number,
+        number_nonblank,
+        squeeze_blank,
+        show_all,
+        show_nonprinting,
+        show_ends,
+        show_tabs,
+        decompress,
+        hex,
+        hex_cols,
+        hex_group,
+        offset,
+        length,
+        reverse,
+        head,
+        tail,
+        lines,
+        bytes,
+        follow,
+        follow_name,
+        pretty,
+    ];
     let examples = vec![
         format!("{program_name} <file1.txt> // Print in console file1.txt content"),
         format!("{program_name}  <file1.txt> <file2.txt> > out.txt // Concatenate two files"),
+        format!("{program_name} header.txt - footer.txt // Print standard input between two files"),
+        format!("{program_name} -n <file1.txt> <file2.txt> // Number lines, continuing across files"),
+        format!("{program_name} -A <file.bin> // Make tabs, line ends and control characters visible"),
+        format!("{program_name} -z logs/*.gz // Print compressed logs, like zcat"),
+        format!("{program_name} -x --offset 1K --length 64 <capture.bin> // Dump 64 bytes from offset 1024"),
+        format!("{program_name} -r <dump.txt> > capture.bin // Turn a hex dump back into binary"),
+        format!("{program_name} --tail 100 <app.log> // Print the last 100 lines without reading the whole file"),
+        format!("{program_name} --lines 100:200 <app.log> // Print lines 100 to 200"),
+        format!("{program_name} --tail 20 -F <app.log> // Print the last lines, then new ones across log rotations"),
+        format!("{program_name} -p src/main.rs // Read code with colours and line numbers"),
         ];
-    let mut cli = Rscli::new(program_name, program_flags, examples);
+    let mut cli = Rscli::new(program_name, ABOUT, SYNOPSIS, program_flags, examples);
     cli.parse_args(&args);
 
-    if !validate(&cli) {
-        cli.usage();
-    }
-
-    start(&cli);
+    let mut out = Output::new();
+    let status = start(&cli, &mut out);
+    out.flush();
+    status.exit();
 }
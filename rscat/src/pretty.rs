@@ -0,0 +1,206 @@
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use ansi_term::Colour;
+use winux_cli::error::{Error, Status};
+use winux_cli::output::Output;
+use crate::highlight::{Highlighter, Syntax};
+use crate::input::Input;
+use crate::range::Range;
+
+/// Used when `$PAGER` is not set. `-R` lets the colours through.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Prints the inputs for reading in a terminal: highlighted by extension,
+/// with line numbers, and through a pager once they do not fit the screen.
+pub fn print(inputs: &[Input], decompress: bool, range: Option<Range>, out: &mut Output) -> Status {
+    let mut status = Status::new();
+    let mut screen = Screen::new(out);
+    let gutter = Colour::Fixed(244).normal();
+
+    for input in inputs {
+        let io_error = |err| Error::Io {
+            path: input.path().to_path_buf(),
+            source: err,
+        };
+        let reader = match input.open(decompress).and_then(|reader| match range {
+            Some(range) => range.apply(reader),
+            None => Ok(reader),
+        }) {
+            Ok(reader) => reader,
+            Err(err) => {
+                status.report(io_error(err));
+                continue;
+            }
+        };
+
+        if inputs.len() > 1 {
+            let header = format!("==> {} <==", input.path().display());
+            screen.write_line(&Colour::White.bold().paint(&header).to_string(), header.chars().count());
+        }
+
+        let mut highlighter = Syntax::for_path(input.path()).map(Highlighter::new);
+        let mut reader = BufReader::new(reader);
+        let mut bytes = vec![];
+        let mut line = String::new();
+        let mut number = 0;
+        while !screen.is_closed() {
+            bytes.clear();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(err) => {
+                    status.report(io_error(err));
+                    break;
+                }
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            let text = text.trim_end_matches(['\n', '\r']);
+            number += 1;
+
+            line.clear();
+            line.push_str(&gutter.paint(format!("{number:>6} │ ")).to_string());
+            match &mut highlighter {
+                Some(highlighter) => highlighter.highlight(text, &mut line),
+                None => line.push_str(text),
+            }
+            screen.write_line(&line, 9 + text.chars().count());
+        }
+    }
+
+    screen.finish();
+    status
+}
+
+/// Writes to the terminal while the output fits on the screen. Past that,
+/// what was held back and everything after goes to the pager.
+struct Screen<'a> {
+    out: &'a mut Output,
+    rows: usize,
+    cols: usize,
+    used_rows: usize,
+    held: Vec<u8>,
+    pager: Pager,
+    /// Whether the pager was quit before the end
+    closed: bool,
+}
+
+enum Pager {
+    /// The output fits on the screen so far.
+    NotNeeded,
+    Running(Child),
+    /// It could not be started, the output is printed as is.
+    Unavailable,
+}
+
+impl<'a> Screen<'a> {
+    fn new(out: &'a mut Output) -> Self {
+        let (rows, cols) = terminal_size();
+        Screen {
+            out,
+            rows,
+            cols,
+            used_rows: 0,
+            held: vec![],
+            pager: Pager::NotNeeded,
+            closed: false,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// `width` is the number of visible characters, to count wrapped lines.
+    fn write_line(&mut self, line: &str, width: usize) {
+        match &mut self.pager {
+            Pager::Running(_) if self.closed => (),
+            Pager::Running(pager) => {
+                let stdin = pager.stdin.as_mut().expect("the pager is spawned with a piped stdin");
+                if writeln!(stdin, "{line}").is_err() {
+                    self.closed = true;
+                }
+            }
+            Pager::Unavailable => {
+                self.out.write_str(line);
+                self.out.newline();
+            }
+            Pager::NotNeeded => {
+                self.held.extend_from_slice(line.as_bytes());
+                self.held.push(b'\n');
+                self.used_rows += width.max(1).div_ceil(self.cols.max(1));
+                // Keep a row for the shell prompt
+                if self.used_rows >= self.rows {
+                    self.start_pager();
+                }
+            }
+        }
+    }
+
+    fn start_pager(&mut self) {
+        let command = env::var("PAGER").unwrap_or_default();
+        let command = if command.trim().is_empty() { DEFAULT_PAGER } else { command.as_str() };
+        let mut words = command.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let mut args: Vec<&str> = words.collect();
+        if program == "less" && args.is_empty() {
+            args.push("-R");
+        }
+
+        match Command::new(program).args(&args).stdin(Stdio::piped()).spawn() {
+            Ok(mut pager) => {
+                let stdin = pager.stdin.as_mut().expect("the pager is spawned with a piped stdin");
+                if stdin.write_all(&self.held).is_err() {
+                    self.closed = true;
+                }
+                self.pager = Pager::Running(pager);
+            }
+            Err(_) => {
+                self.out.write_bytes(&self.held);
+                self.pager = Pager::Unavailable;
+            }
+        }
+        self.held.clear();
+    }
+
+    fn finish(self) {
+        match self.pager {
+            Pager::NotNeeded => self.out.write_bytes(&self.held),
+            Pager::Running(mut pager) => {
+                drop(pager.stdin.take());
+                let _ = pager.wait();
+            }
+            Pager::Unavailable => (),
+        }
+    }
+}
+
+/// Rows and columns of the terminal on standard output.
+#[cfg(target_os = "linux")]
+fn terminal_size() -> (usize, usize) {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only fills in `size`
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_row > 0 && size.ws_col > 0 {
+        (size.ws_row as usize, size.ws_col as usize)
+    } else {
+        terminal_size_from_env()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn terminal_size() -> (usize, usize) {
+    terminal_size_from_env()
+}
+
+/// `LINES` and `COLUMNS` as set by most shells, 24x80 otherwise.
+fn terminal_size_from_env() -> (usize, usize) {
+    let var = |name, default| env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default);
+    (var("LINES", 24), var("COLUMNS", 80))
+}
+
@@ -0,0 +1,270 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use winux_cli::rscli::parse_size;
+use crate::input::Reader;
+
+/// Bytes read at a time when looking for the last lines from the end.
+const TAIL_BLOCK: usize = 64 * 1024;
+
+/// The part of each input to print. Line and byte numbers start at 1 and
+/// both ends are included, like `cut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Range {
+    Lines { first: u64, last: Option<u64> },
+    Bytes { first: u64, last: Option<u64> },
+    /// The last N lines.
+    Tail(u64),
+}
+
+/// Parses `A:B`, `A:`, `:B` or a single `A`.
+pub fn parse_bounds(text: &str) -> Result<(u64, Option<u64>), String> {
+    let number = |s: &str| parse_size(s).map_err(|_| format!("invalid range '{text}', expected A:B"));
+    let (first, last) = match text.split_once(':') {
+        Some((first, last)) => (first, last),
+        None => (text, text),
+    };
+    let first = if first.is_empty() { 1 } else { number(first)? };
+    let last = if last.is_empty() { None } else { Some(number(last)?) };
+
+    if first == 0 {
+        return Err(format!("invalid range '{text}', numbering starts at 1"));
+    }
+    if last.is_some_and(|last| last < first) {
+        return Err(format!("invalid range '{text}', the end comes before the start"));
+    }
+    Ok((first, last))
+}
+
+impl Range {
+    /// Wraps `reader` so that it only yields the selected part.
+    pub fn apply(self, reader: Reader) -> io::Result<Reader> {
+        match self {
+            Range::Lines { first, last } => Ok(Reader::Decoded(Box::new(LineRange {
+                inner: reader,
+                line: 1,
+                first,
+                last,
+            }))),
+            Range::Bytes { first, last } => bytes(reader, first, last),
+            Range::Tail(count) => tail(reader, count),
+        }
+    }
+}
+
+/// Skips to byte `first`, seeking when the input allows it.
+fn bytes(mut reader: Reader, first: u64, last: Option<u64>) -> io::Result<Reader> {
+    let skip = first - 1;
+    let seeked = match &mut reader {
+        Reader::File(file) if skip > 0 => file.seek(SeekFrom::Start(skip)).is_ok(),
+        _ => false,
+    };
+    if !seeked {
+        io::copy(&mut (&mut reader).take(skip), &mut io::sink())?;
+        // Stdin may hold read ahead bytes that a kernel side copy would miss
+        reader = Reader::Decoded(Box::new(reader));
+    }
+
+    match last {
+        Some(last) => Ok(Reader::Decoded(Box::new(reader.take(last - first + 1)))),
+        None => Ok(reader),
+    }
+}
+
+/// The last `count` lines. Regular files are searched backwards from the
+/// end so only the tail is read, other inputs are read through keeping the
+/// last lines in memory.
+fn tail(reader: Reader, count: u64) -> io::Result<Reader> {
+    if let Reader::File(mut file) = reader {
+        let metadata = file.metadata()?;
+        // Files in /proc claim to be empty, read them through
+        if metadata.is_file() && metadata.len() > 0 {
+            let start = tail_start(&mut file, metadata.len(), count)?;
+            file.seek(SeekFrom::Start(start))?;
+            return Ok(Reader::File(file));
+        }
+        return tail_in_memory(Reader::File(file), count);
+    }
+    tail_in_memory(reader, count)
+}
+
+/// The offset where the last `count` lines of `file` begin.
+fn tail_start(file: &mut File, len: u64, count: u64) -> io::Result<u64> {
+    if count == 0 {
+        return Ok(len);
+    }
+
+    let mut buffer = vec![0; TAIL_BLOCK];
+    let mut newlines = 0;
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_BLOCK as u64);
+        let block = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+
+        for (i, &byte) in block.iter().enumerate().rev() {
+            let offset = start + i as u64;
+            // The newline ending the file closes the last line, it does not start one
+            if byte == b'\n' && offset != len - 1 {
+                newlines += 1;
+                if newlines == count {
+                    return Ok(offset + 1);
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+fn tail_in_memory(reader: Reader, count: u64) -> io::Result<Reader> {
+    let mut reader = BufReader::new(reader);
+    let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+    loop {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        lines.push_back(line);
+        if lines.len() as u64 > count {
+            lines.pop_front();
+        }
+    }
+    Ok(Reader::Decoded(Box::new(Cursor::new(lines.into_iter().flatten().collect::<Vec<u8>>()))))
+}
+
+/// Yields the lines from `first` to `last`, and stops reading after that.
+struct LineRange {
+    inner: Reader,
+    /// Number of the line the next byte belongs to
+    line: u64,
+    first: u64,
+    last: Option<u64>,
+}
+
+impl LineRange {
+    fn past_end(&self) -> bool {
+        self.last.is_some_and(|last| self.line > last)
+    }
+}
+
+impl Read for LineRange {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.past_end() {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+
+            // Keep the selected lines at the front of `buf`
+            let mut kept = 0;
+            let mut pos = 0;
+            while pos < n && !self.past_end() {
+                let end = buf[pos..n].iter().position(|&b| b == b'\n').map_or(n, |i| pos + i + 1);
+                let ends_line = buf[end - 1] == b'\n';
+                if self.line >= self.first {
+                    buf.copy_within(pos..end, kept);
+                    kept += end - pos;
+                }
+                if ends_line {
+                    self.line += 1;
+                }
+                pos = end;
+            }
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::{env, fs, process};
+
+    fn read(range: Range, input: &[u8]) -> Vec<u8> {
+        let reader = Reader::Decoded(Box::new(Cursor::new(input.to_vec())));
+        let mut bytes = vec![];
+        range.apply(reader).unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// `tail_start` on a real file holding `content`.
+    fn tail_start_of(content: &[u8], count: u64) -> u64 {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let n = FILES.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("rscat-tail-{}-{n}", process::id()));
+        fs::write(&path, content).unwrap();
+        let start = tail_start(&mut File::open(&path).unwrap(), content.len() as u64, count);
+        fs::remove_file(&path).unwrap();
+        start.unwrap()
+    }
+
+    #[test]
+    fn bounds() {
+        assert_eq!(parse_bounds("2:4"), Ok((2, Some(4))));
+        assert_eq!(parse_bounds("3"), Ok((3, Some(3))));
+        assert_eq!(parse_bounds("3:"), Ok((3, None)));
+        assert_eq!(parse_bounds(":5"), Ok((1, Some(5))));
+        assert_eq!(parse_bounds(":"), Ok((1, None)));
+        assert_eq!(parse_bounds("1K:"), Ok((1024, None)));
+    }
+
+    #[test]
+    fn invalid_bounds() {
+        assert!(parse_bounds("0:3").is_err());
+        assert!(parse_bounds("0").is_err());
+        assert!(parse_bounds("5:2").is_err());
+        assert!(parse_bounds("a:b").is_err());
+        assert!(parse_bounds("-1").is_err());
+    }
+
+    #[test]
+    fn lines() {
+        let input = b"1\n2\n3\n4\n5";
+        assert_eq!(read(Range::Lines { first: 2, last: Some(3) }, input), b"2\n3\n");
+        assert_eq!(read(Range::Lines { first: 4, last: None }, input), b"4\n5");
+        assert_eq!(read(Range::Lines { first: 1, last: Some(1) }, input), b"1\n");
+        assert_eq!(read(Range::Lines { first: 9, last: None }, input), b"");
+    }
+
+    #[test]
+    fn bytes() {
+        let input = b"abcdef";
+        assert_eq!(read(Range::Bytes { first: 2, last: Some(4) }, input), b"bcd");
+        assert_eq!(read(Range::Bytes { first: 5, last: None }, input), b"ef");
+        assert_eq!(read(Range::Bytes { first: 1, last: Some(99) }, input), b"abcdef");
+    }
+
+    #[test]
+    fn tail_in_memory() {
+        let input = b"1\n2\n3\n";
+        assert_eq!(read(Range::Tail(2), input), b"2\n3\n");
+        assert_eq!(read(Range::Tail(9), input), b"1\n2\n3\n");
+        assert_eq!(read(Range::Tail(0), input), b"");
+        assert_eq!(read(Range::Tail(1), b"1\n2"), b"2");
+    }
+
+    #[test]
+    fn tail_from_end_of_file() {
+        assert_eq!(tail_start_of(b"1\n2\n3\n", 2), 2);
+        assert_eq!(tail_start_of(b"1\n2\n3\n", 0), 6);
+        assert_eq!(tail_start_of(b"1\n2\n3\n", 9), 0);
+        // Without a final newline the last line still counts as one
+        assert_eq!(tail_start_of(b"1\n2\n3", 1), 4);
+        assert_eq!(tail_start_of(b"\n\n", 1), 1);
+    }
+
+    #[test]
+    fn tail_across_blocks() {
+        let mut content = vec![b'x'; TAIL_BLOCK + 10];
+        content[5] = b'\n';
+        content.extend_from_slice(b"\nlast\n");
+        assert_eq!(tail_start_of(&content, 1), TAIL_BLOCK as u64 + 11);
+        assert_eq!(tail_start_of(&content, 2), 6);
+    }
+}
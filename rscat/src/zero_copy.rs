@@ -0,0 +1,109 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::process::exit;
+use std::ptr;
+use winux_cli::output::EXIT_BROKEN_PIPE;
+
+const STDOUT: RawFd = libc::STDOUT_FILENO;
+/// Bytes asked for per system call, the kernel moves at most that much.
+const MAX_TRANSFER: usize = 1 << 30;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Regular,
+    Pipe,
+    Other,
+}
+
+#[derive(Clone, Copy)]
+enum Method {
+    /// File to file, can share extents on filesystems that support it.
+    CopyFileRange,
+    /// From or to a pipe.
+    Splice,
+    /// From anything that can be mapped, to anything.
+    Sendfile,
+}
+
+/// Copies `input` to standard output without going through user space.
+/// Returns `false` when the kernel cannot do it for this pair of files,
+/// leaving the rest of the input to a regular read/write loop. Anything
+/// buffered in `Output` must be flushed before.
+pub fn copy(input: BorrowedFd) -> io::Result<bool> {
+    let input = input.as_raw_fd();
+    let input_kind = kind(input)?;
+    let output_kind = kind(STDOUT)?;
+
+    let mut methods = vec![];
+    if input_kind == Kind::Regular && output_kind == Kind::Regular {
+        methods.push(Method::CopyFileRange);
+    }
+    if input_kind == Kind::Pipe || output_kind == Kind::Pipe {
+        methods.push(Method::Splice);
+    }
+    if input_kind != Kind::Pipe {
+        methods.push(Method::Sendfile);
+    }
+
+    for method in methods {
+        if transfer(method, input)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Moves data with `method` until the end of `input`. The file offsets are
+/// advanced as we go, so on `false` whatever comes next picks up where this
+/// stopped.
+fn transfer(method: Method, input: RawFd) -> io::Result<bool> {
+    let mut copied = false;
+    loop {
+        // SAFETY: plain system calls on open descriptors, with null offsets
+        // so the kernel uses and updates the file positions
+        let n = unsafe {
+            match method {
+                Method::CopyFileRange => {
+                    libc::copy_file_range(input, ptr::null_mut(), STDOUT, ptr::null_mut(), MAX_TRANSFER, 0)
+                }
+                Method::Splice => libc::splice(input, ptr::null_mut(), STDOUT, ptr::null_mut(), MAX_TRANSFER, 0),
+                Method::Sendfile => libc::sendfile(STDOUT, input, ptr::null_mut(), MAX_TRANSFER),
+            }
+        };
+
+        match n {
+            // Files in /proc or /sys claim to be empty, read them instead
+            0 if !copied => return Ok(false),
+            0 => return Ok(true),
+            n if n > 0 => copied = true,
+            _ => {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) => (),
+                    Some(libc::EPIPE) => exit(EXIT_BROKEN_PIPE),
+                    // Not supported for these files, e.g. across filesystems
+                    // or with an output opened in append mode
+                    Some(libc::EINVAL | libc::ENOSYS | libc::EXDEV | libc::EOPNOTSUPP | libc::EBADF) => {
+                        return Ok(false);
+                    }
+                    _ => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+fn kind(fd: RawFd) -> io::Result<Kind> {
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `stat` is only read after fstat filled it in
+    if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mode = unsafe { stat.assume_init() }.st_mode & libc::S_IFMT;
+    Ok(match mode {
+        libc::S_IFREG => Kind::Regular,
+        libc::S_IFIFO => Kind::Pipe,
+        _ => Kind::Other,
+    })
+}
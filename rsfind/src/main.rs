@@ -1,66 +1,90 @@
-mod rscli;
-
 use std::path::{Path, PathBuf};
 use std::env;
+use std::ffi::OsString;
 use std::fs;
-use ansi_term::Colour;
-use rscli::{Flag, Rscli};
+use winux_cli::error::{Error, Status};
+use winux_cli::os;
+use winux_cli::output::Output;
+use winux_cli::rscli::{Arity, Flag, Rscli, ValueKind};
 use walkdir::{WalkDir, DirEntry};
 
+const ABOUT: &str = "Find files whose path contains a pattern.";
+const SYNOPSIS: &str = "[OPTIONS] PATTERN... [-d DIR]...";
 const DIR_FLAG: &str = "d";
+const DIR_LONG: &str = "dir";
 const DIR_DESC: &str = "Specify directory to search into";
 const RECURSIVE_FLAG: &str = "r";
+const RECURSIVE_LONG: &str = "recursive";
 const RECURSIVE_DESC: &str = "Find files recursively";
 const EXCLUDE_FLAG: &str = "e";
+const EXCLUDE_LONG: &str = "exclude";
 const EXCLUDE_DESC: &str = "Exclude dir or files";
 const FOLLOW_SYMLINK_FLAG: &str = "s";
+const FOLLOW_SYMLINK_LONG: &str = "follow";
 const FOLLOW_SYMLINK_DESC: &str = "Follow symlink";
+const MAX_DEPTH_FLAG: &str = "max-depth";
+const MAX_DEPTH_DESC: &str = "Descend at most NUM directories below the search dirs";
 
 fn validate(drcli: &Rscli) -> bool {
-    if drcli.free_args.len() == 0 {
-        return false;
-    }
-
-    return true;
+    !drcli.free_args.is_empty()
 }
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<OsString> = env::args_os().collect();
     
     let dir = Flag {
-        id: DIR_FLAG, 
+        id: DIR_FLAG,
+        long: Some(DIR_LONG),
         description: DIR_DESC,
-        values: vec![]
+        arity: Arity::One,
+        kind: ValueKind::Path,
+        ..Default::default()
     };
     let recursive = Flag {
-        id: RECURSIVE_FLAG, 
+        id: RECURSIVE_FLAG,
+        long: Some(RECURSIVE_LONG),
         description: RECURSIVE_DESC,
-        values: vec![]
+        ..Default::default()
     };
     let exclude = Flag {
-        id: EXCLUDE_FLAG, 
+        id: EXCLUDE_FLAG,
+        long: Some(EXCLUDE_LONG),
         description: EXCLUDE_DESC,
-        values: vec![]
+        arity: Arity::One,
+        kind: ValueKind::Path,
+        ..Default::default()
     };
     let symlink = Flag {
-        id: FOLLOW_SYMLINK_FLAG, 
+        id: FOLLOW_SYMLINK_FLAG,
+        long: Some(FOLLOW_SYMLINK_LONG),
         description: FOLLOW_SYMLINK_DESC,
-        values: vec![]
+        ..Default::default()
+    };
+    let max_depth = Flag {
+        id: MAX_DEPTH_FLAG,
+        long: Some(MAX_DEPTH_FLAG),
+        description: MAX_DEPTH_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
     };
 
-    let program_name = args.get(0).expect("[ERROR] Program name should exist.").clone();
-    let program_flags = vec![dir, recursive, exclude, symlink];
+    let program_name = args.first().expect("[ERROR] Program name should exist.").to_string_lossy().into_owned();
+    let program_flags = vec![dir, recursive, exclude, symlink, max_depth];
     let examples = vec![
         format!("{program_name} \"pattern\" -d <dir>"),
         format!("{program_name} \"pattern\" -r"),
         ];
-    let mut cli = Rscli::new(program_name, program_flags, examples);
+    let mut cli = Rscli::new(program_name, ABOUT, SYNOPSIS, program_flags, examples);
     cli.parse_args(&args);
 
     if !validate(&cli) {
         cli.usage();
     }
 
-    search(&cli);
+    let mut out = Output::new();
+    let status = search(&cli, &mut out);
+    out.flush();
+    status.exit();
 }
 
 
@@ -76,70 +100,66 @@ fn filter(entry: &DirEntry, cli: &Rscli) -> bool {
         }
         if let Some(exclude) = exclude {
             if let Ok(file_name) =  fs::canonicalize(entry.path()) {
-                for e in exclude.values.clone() {
-                    if file_name.to_str().unwrap().contains(&e) {
+                for e in exclude.values.iter() {
+                    if os::contains(file_name.as_os_str(), e) {
                         return false;
                     }
                 }
             }
         }
     }
-    return true;
+    true
 }
 
-fn search(cli: &Rscli) {
+fn search(cli: &Rscli, out: &mut Output) -> Status {
     let paths = get_search_paths(cli);
+    let mut status = Status::for_search();
 
     for path in paths {
-        search_in_path(&path, cli);
+        search_in_path(&path, cli, &mut status, out);
     }
+    status
 }
 
 fn get_search_paths(cli: &Rscli) -> Vec<PathBuf> {
-    if let Some(path_flag) = cli.find_user_flag_by_id(DIR_FLAG) {
-        path_flag.values.iter().map(PathBuf::from).collect()
+    if cli.find_user_flag_by_id(DIR_FLAG).is_some() {
+        cli.get_paths(DIR_FLAG)
     } else {
         vec![PathBuf::from(".")]
     }
 }
 
-fn search_in_path(path: &Path, cli: &Rscli) {
-    let walker = WalkDir::new(path)
-        .follow_links(cli.find_user_flag_by_id(FOLLOW_SYMLINK_FLAG).is_some())
+fn search_in_path(path: &Path, cli: &Rscli, status: &mut Status, out: &mut Output) {
+    let mut walker = WalkDir::new(path)
+        .follow_links(cli.find_user_flag_by_id(FOLLOW_SYMLINK_FLAG).is_some());
+    if let Some(max_depth) = cli.get_usize(MAX_DEPTH_FLAG) {
+        walker = walker.max_depth(max_depth);
+    }
+    let walker = walker
         .into_iter()
         .filter_entry(|e| filter(e, cli));
 
     for entry in walker {
-        if let Ok(e) = entry {
-            process_entry(&e, cli);
+        match entry {
+            Ok(e) => process_entry(&e, cli, status, out),
+            Err(err) => status.report(Error::from_walk(err, path)),
         }
     }
 }
 
-fn process_entry(entry: &DirEntry, cli: &Rscli) {
+fn process_entry(entry: &DirEntry, cli: &Rscli, status: &mut Status, out: &mut Output) {
     match entry.path().canonicalize() {
-        Ok(absolute) => {       
-            match_file(&absolute, cli);
-        }
-        Err(_) => return,
+        Ok(absolute) => match_file(&absolute, cli, status, out),
+        Err(err) => status.report(Error::Io { path: entry.path().to_path_buf(), source: err }),
     }
 }
 
-fn match_file(path: &PathBuf, cli: &Rscli) {
+fn match_file(path: &Path, cli: &Rscli, status: &mut Status, out: &mut Output) {
     for pattern in cli.free_args.iter() {
-        if path.to_str().unwrap().contains(pattern) {
-            print_path(path);
-            println!();
+        if os::contains(path.as_os_str(), pattern) {
+            os::print_path(path, out);
+            out.newline();
+            status.set_matched();
         }
     }
 }
-
-fn print_path(path: &PathBuf) {
-    let mut path_str = path.to_string_lossy().to_string();
-    if path_str.starts_with(r"\\?\") {
-        path_str = path_str[4..].to_string();
-    }
-    
-    let style = Colour::Purple.bold();
-    print!("{}", style.paint(path_str));
-}
\ No newline at end of file
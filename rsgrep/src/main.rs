@@ -1,33 +1,85 @@
-use std::{env, fs, path::{Path, PathBuf}, usize};
-mod rscli;
-use rscli::{Rscli, Flag};
+use std::{env, ffi::OsString, fs, path::{Path, PathBuf}};
+use winux_cli::error::{Error, Status};
+use winux_cli::os;
+use winux_cli::output::Output;
+use winux_cli::rscli::{Arity, Rscli, Flag, ValueKind};
 use walkdir::{WalkDir, DirEntry};
-use ansi_term::Colour;
-use regex::Regex;
+use ansi_term::{Colour, Style};
+use regex::{Regex, RegexSet};
 
 
+const ABOUT: &str = "Search files for lines containing a pattern.";
+const SYNOPSIS: &str = "[OPTIONS] [PATTERN]... [-R REGEX]... [-p PATH]...";
 const REGEX_FLAG: &str = "R";
-const REGEX_DESC: &str = "Match with regex.";
+const REGEX_LONG: &str = "regex";
+const REGEX_DESC: &str = "Match with regex, as well as any PATTERN operands.";
 const RECURSIVE_FLAG: &str = "r";
+const RECURSIVE_LONG: &str = "recursive";
 const RECURSIVE_DESC: &str = "Search recursively.";
 const LINE_NUMBER_FLAG: &str = "n";
+const LINE_NUMBER_LONG: &str = "line-number";
 const LINE_NUMBER_DESC: &str = "Print line number.";
 const NO_MATCH_ONLY_FLAG: &str = "L";
+const NO_MATCH_ONLY_LONG: &str = "files-without-match";
 const NO_MATCH_ONLY_DESC: &str = "Print file names without match.";
 const MATCH_ONLY_FLAG: &str = "l";
+const MATCH_ONLY_LONG: &str = "files-with-matches";
 const MATCH_ONLY_DESC: &str = "Print file names with match.";
 const FOLLOW_SYMLINK_FLAG: &str = "s";
+const FOLLOW_SYMLINK_LONG: &str = "follow";
 const FOLLOW_SYMLINK_DESC: &str = "Follow symbolic link.";
 const EXCLUDE_FLAG: &str = "e";
+const EXCLUDE_LONG: &str = "exclude";
 const EXCLUDE_DESC: &str = "Exclude files/directories.";
 const PATH_FLAG: &str = "p";
+const PATH_LONG: &str = "path";
 const PATH_DESC: &str = "Specify paths to search into.";
+const MAX_DEPTH_FLAG: &str = "max-depth";
+const MAX_DEPTH_DESC: &str = "Descend at most NUM directories below the search paths.";
+const AFTER_CONTEXT_FLAG: &str = "A";
+const AFTER_CONTEXT_LONG: &str = "after-context";
+const AFTER_CONTEXT_DESC: &str = "Print NUM lines of trailing context after matching lines.";
+const BEFORE_CONTEXT_FLAG: &str = "B";
+const BEFORE_CONTEXT_LONG: &str = "before-context";
+const BEFORE_CONTEXT_DESC: &str = "Print NUM lines of leading context before matching lines.";
+const CONTEXT_FLAG: &str = "C";
+const CONTEXT_LONG: &str = "context";
+const CONTEXT_DESC: &str = "Print NUM lines of context around matching lines.";
+const ONLY_MATCHING_FLAG: &str = "o";
+const ONLY_MATCHING_LONG: &str = "only-matching";
+const ONLY_MATCHING_DESC: &str = "Print only the matched parts, each on its own line.";
+const COLUMN_FLAG: &str = "column";
+const COLUMN_DESC: &str = "Print the 1-based column of the first match, or of each match with -o.";
+const BYTE_OFFSET_FLAG: &str = "b";
+const BYTE_OFFSET_LONG: &str = "byte-offset";
+const BYTE_OFFSET_DESC: &str = "Print the 0-based byte offset of each line in the file, or of each match with -o.";
+const VIMGREP_FLAG: &str = "vimgrep";
+const VIMGREP_DESC: &str = "Print each match as path:line:column:text, for editor quickfix lists.";
+const COLOR_FLAG: &str = "color";
+const COLOR_DESC: &str = "Colour the output: auto (on a terminal), always or never.";
+const COLOR_CHOICES: &[&str] = &["auto", "always", "never"];
+const IGNORE_CASE_FLAG: &str = "i";
+const IGNORE_CASE_LONG: &str = "ignore-case";
+const IGNORE_CASE_DESC: &str = "Ignore case distinctions in patterns and text.";
+const SMART_CASE_FLAG: &str = "S";
+const SMART_CASE_LONG: &str = "smart-case";
+const SMART_CASE_DESC: &str = "Ignore case unless the pattern contains an uppercase letter.";
+const WORD_FLAG: &str = "w";
+const WORD_LONG: &str = "word-regexp";
+const WORD_DESC: &str = "Only match whole words.";
+const LINE_FLAG: &str = "x";
+const LINE_LONG: &str = "line-regexp";
+const LINE_DESC: &str = "Only match whole lines.";
 
 struct LineMatch {
     line: String,
     line_number: usize,
-    start: usize,
-    end: usize
+    /// Where the line starts in the file
+    byte_offset: usize,
+    /// Byte ranges of every match on the line, sorted and not overlapping
+    spans: Vec<(usize, usize)>,
+    /// Printed around a match for -A, -B and -C, without matching itself
+    context: bool,
 }
 
 struct Match {
@@ -35,89 +87,345 @@ struct Match {
     lines: Vec<LineMatch>,
 }
 
+/// How patterns match, on top of their own syntax.
+#[derive(Clone, Copy, Default)]
+struct MatchOptions {
+    ignore_case: bool,
+    smart_case: bool,
+    word: bool,
+    line: bool,
+}
+
+/// The patterns, compiled once for the whole search. Fixed strings are
+/// escaped into regexes so the options apply the same way to both, and a
+/// line matches if any pattern of either kind does.
+struct Matcher {
+    /// Tells in a single pass over a line which patterns match it
+    set: RegexSet,
+    /// To then find where those patterns match
+    regexes: Vec<Regex>,
+}
+
+impl Matcher {
+    fn new(regexes: &[&str], fixed: &[&str], options: MatchOptions) -> Result<Matcher, regex::Error> {
+        // Report syntax errors against the patterns as they were written
+        RegexSet::new(regexes)?;
+        let patterns: Vec<String> = regexes
+            .iter()
+            .map(|pattern| to_regex(pattern, false, options))
+            .chain(fixed.iter().map(|pattern| to_regex(pattern, true, options)))
+            .collect();
+        Ok(Matcher {
+            set: RegexSet::new(&patterns)?,
+            regexes: patterns.iter().map(|pattern| Regex::new(pattern)).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn to_regex(pattern: &str, fixed: bool, options: MatchOptions) -> String {
+    let mut regex = if fixed { regex::escape(pattern) } else { pattern.to_string() };
+    if options.line {
+        regex = format!("^(?:{regex})$");
+    } else if options.word {
+        // Unlike \b, also right for patterns that start or end with a non-word character
+        regex = format!(r"\b{{start-half}}(?:{regex})\b{{end-half}}");
+    }
+    // The regex engine folds case with Unicode's simple case folding
+    let ignore_case = options.ignore_case || (options.smart_case && !has_uppercase(pattern, fixed));
+    if ignore_case {
+        regex = format!("(?i){regex}");
+    }
+    regex
+}
+
+/// Whether the pattern asks for an uppercase letter. In a regex, escapes
+/// such as \W or \p{Lu} are classes, not letters.
+fn has_uppercase(pattern: &str, fixed: bool) -> bool {
+    if fixed {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some('p' | 'P') = chars.next() {
+                    if chars.clone().next() == Some('{') {
+                        chars.find(|&c| c == '}');
+                    } else {
+                        chars.next();
+                    }
+                }
+            }
+            c if c.is_uppercase() => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<OsString> = env::args_os().collect();
     
     let regex = Flag {
         id: REGEX_FLAG,
+        long: Some(REGEX_LONG),
         description: REGEX_DESC,
-        values: vec![],
+        arity: Arity::One,
+        ..Default::default()
     };
 
     let recursive = Flag {
         id: RECURSIVE_FLAG,
+        long: Some(RECURSIVE_LONG),
         description: RECURSIVE_DESC,
-        values: vec![],
+        ..Default::default()
     };
     
     let line_number = Flag {
         id: LINE_NUMBER_FLAG,
+        long: Some(LINE_NUMBER_LONG),
         description: LINE_NUMBER_DESC,
-        values: vec![],
+        ..Default::default()
     };
 
     let no_match_only = Flag {
         id: NO_MATCH_ONLY_FLAG,
+        long: Some(NO_MATCH_ONLY_LONG),
         description: NO_MATCH_ONLY_DESC,
-        values: vec![],
+        ..Default::default()
     };
 
     let match_only = Flag {
         id: MATCH_ONLY_FLAG,
+        long: Some(MATCH_ONLY_LONG),
         description: MATCH_ONLY_DESC,
-        values: vec![],
+        ..Default::default()
     };
 
     let follow_symklink = Flag {
         id: FOLLOW_SYMLINK_FLAG,
+        long: Some(FOLLOW_SYMLINK_LONG),
         description: FOLLOW_SYMLINK_DESC,
-        values: vec![],
+        ..Default::default()
     };
 
     let exclude = Flag {
         id: EXCLUDE_FLAG,
+        long: Some(EXCLUDE_LONG),
         description: EXCLUDE_DESC,
-        values: vec![],
+        arity: Arity::One,
+        kind: ValueKind::Path,
+        ..Default::default()
     };
 
     let paths = Flag {
         id: PATH_FLAG,
+        long: Some(PATH_LONG),
         description: PATH_DESC,
-        values: vec![],
+        arity: Arity::One,
+        kind: ValueKind::Path,
+        ..Default::default()
+    };
+
+    let max_depth = Flag {
+        id: MAX_DEPTH_FLAG,
+        long: Some(MAX_DEPTH_FLAG),
+        description: MAX_DEPTH_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
     };
 
-    let program_name = args.get(0).expect("[ERROR] Program name should exist.").clone();
-    let program_flags = vec![regex, recursive, line_number, no_match_only, match_only, follow_symklink, exclude, paths];
+    let after_context = Flag {
+        id: AFTER_CONTEXT_FLAG,
+        long: Some(AFTER_CONTEXT_LONG),
+        description: AFTER_CONTEXT_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+
+    let before_context = Flag {
+        id: BEFORE_CONTEXT_FLAG,
+        long: Some(BEFORE_CONTEXT_LONG),
+        description: BEFORE_CONTEXT_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+
+    let context = Flag {
+        id: CONTEXT_FLAG,
+        long: Some(CONTEXT_LONG),
+        description: CONTEXT_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Integer,
+        ..Default::default()
+    };
+
+    let only_matching = Flag {
+        id: ONLY_MATCHING_FLAG,
+        long: Some(ONLY_MATCHING_LONG),
+        description: ONLY_MATCHING_DESC,
+        ..Default::default()
+    };
+
+    let column = Flag {
+        id: COLUMN_FLAG,
+        long: Some(COLUMN_FLAG),
+        description: COLUMN_DESC,
+        ..Default::default()
+    };
+
+    let byte_offset = Flag {
+        id: BYTE_OFFSET_FLAG,
+        long: Some(BYTE_OFFSET_LONG),
+        description: BYTE_OFFSET_DESC,
+        ..Default::default()
+    };
+
+    let vimgrep = Flag {
+        id: VIMGREP_FLAG,
+        long: Some(VIMGREP_FLAG),
+        description: VIMGREP_DESC,
+        ..Default::default()
+    };
+
+    let color = Flag {
+        id: COLOR_FLAG,
+        long: Some(COLOR_FLAG),
+        description: COLOR_DESC,
+        arity: Arity::One,
+        kind: ValueKind::Choice(COLOR_CHOICES),
+        ..Default::default()
+    };
+
+    let ignore_case = Flag {
+        id: IGNORE_CASE_FLAG,
+        long: Some(IGNORE_CASE_LONG),
+        description: IGNORE_CASE_DESC,
+        ..Default::default()
+    };
+
+    let smart_case = Flag {
+        id: SMART_CASE_FLAG,
+        long: Some(SMART_CASE_LONG),
+        description: SMART_CASE_DESC,
+        ..Default::default()
+    };
+
+    let word = Flag {
+        id: WORD_FLAG,
+        long: Some(WORD_LONG),
+        description: WORD_DESC,
+        ..Default::default()
+    };
+
+    let line = Flag {
+        id: LINE_FLAG,
+        long: Some(LINE_LONG),
+        description: LINE_DESC,
+        ..Default::default()
+    };
+
+    let program_name = args.first().expect("[ERROR] Program name should exist.").to_string_lossy().into_owned();
+    let program_flags = vec![
+        regex,
+        recursive,
+        line_number,
+        no_match_only,
+        match_only,
+        follow_symklink,
+        exclude,
+        paths,
+        max_depth,
+        after_context,
+        before_context,
+        context,
+        only_matching,
+        column,
+        byte_offset,
+        vimgrep,
+        color,
+        ignore_case,
+        smart_case,
+        word,
+        line,
+    ];
     let examples = vec![
         format!("{program_name} 'foreach' -rn  // Print each line and number containing 'foreach'"), 
         format!("{program_name} 'foreach' -rL  // Print each file that does not contain 'foreach'"), 
         format!("{program_name} 'foreach' 'another pattern' -p /home -rL  // Print each file that does not contain 'foreach'"), 
+        format!("{program_name} 'foreach' --recursive --line-number  // Same as -rn"), 
+        format!("{program_name} 'foreach' -rn -C 3  // Also print the 3 lines before and after each match"), 
+        format!("{program_name} -R '[0-9]+' -ro  // Print every number found, one per line"), 
+        format!("{program_name} 'TODO' -r --vimgrep  // List matches for vim's quickfix, e.g. with :cexpr"), 
+        format!("{program_name} 'error' -rSw  // Match the word 'error' in any case, but 'Error' exactly"), 
         ];
-    let mut cli = Rscli::new(program_name, program_flags, examples);
+    let mut cli = Rscli::new(program_name, ABOUT, SYNOPSIS, program_flags, examples);
     cli.parse_args(&args);
 
     if !validate(&cli) {
         cli.usage();
     }
-
-    search(&cli);
-
+    let matcher = get_matcher(&cli);
+
+    let mut out = Output::new();
+    let colours = get_colours(&cli, out.is_terminal());
+    out.set_colours(colours);
+    let status = search(&cli, &matcher, &mut out);
+    out.flush();
+    status.exit();
 }
 
 fn validate(cli: &Rscli) -> bool {
-    if cli.matched_flags.len() == 0 {
+    if cli.matched_flags.is_empty() {
         return false;
     }
 
-    if cli.free_args.len() == 0 && cli.find_user_flag_by_id(REGEX_FLAG).is_none(){
+    if cli.free_args.is_empty() && cli.find_user_flag_by_id(REGEX_FLAG).is_none(){
         return false;
     }
 
-    return true;
+    true
 }
 
 
 
+/// Compiles the -R patterns and the fixed strings given as operands,
+/// exiting on invalid syntax before any file is searched.
+fn get_matcher(cli: &Rscli) -> Matcher {
+    let options = MatchOptions {
+        ignore_case: cli.find_user_flag_by_id(IGNORE_CASE_FLAG).is_some(),
+        smart_case: cli.find_user_flag_by_id(SMART_CASE_FLAG).is_some(),
+        word: cli.find_user_flag_by_id(WORD_FLAG).is_some(),
+        line: cli.find_user_flag_by_id(LINE_FLAG).is_some(),
+    };
+    let regexes = cli.get_strs(REGEX_FLAG);
+    let fixed: Vec<&str> = cli.free_args
+        .iter()
+        .map(|arg| arg.to_str().unwrap_or_else(|| {
+            cli.fail(Error::Usage(format!("Pattern is not valid UTF-8: {}", arg.to_string_lossy())))
+        }))
+        .collect();
+    match Matcher::new(&regexes, &fixed, options) {
+        Ok(matcher) => matcher,
+        Err(err) => cli.fail(Error::Usage(format!("invalid regex: {err}"))),
+    }
+}
+
+/// Editors parse --vimgrep output, so it never has colours.
+fn get_colours(cli: &Rscli, terminal: bool) -> bool {
+    if cli.find_user_flag_by_id(VIMGREP_FLAG).is_some() {
+        return false;
+    }
+    match cli.get_str(COLOR_FLAG) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => terminal,
+    }
+}
+
 fn filter(entry: &DirEntry, cli: &Rscli) -> bool {
     
     let recursive = cli.find_user_flag_by_id(RECURSIVE_FLAG);
@@ -129,174 +437,493 @@ fn filter(entry: &DirEntry, cli: &Rscli) -> bool {
         }
         if let Some(exclude) = exclude {
             if let Ok(file_name) =  fs::canonicalize(entry.path()) {
-                for e in exclude.values.clone() {
-                    if file_name.to_str().unwrap().contains(&e) {
+                for e in exclude.values.iter() {
+                    if os::contains(file_name.as_os_str(), e) {
                         return false;
                     }
                 }
             }
         }
     }
-    return true;
+    true
 }
 
-fn search(cli: &Rscli) {
+fn search(cli: &Rscli, matcher: &Matcher, out: &mut Output) -> Status {
     let paths = get_search_paths(cli);
     let follow_symlink = cli.find_user_flag_by_id(FOLLOW_SYMLINK_FLAG).is_some();
+    let mut status = Status::for_search();
 
     for path in paths {
-        search_in_path(&path, cli, follow_symlink);
+        search_in_path(&path, cli, matcher, follow_symlink, &mut status, out);
     }
+    status
 }
 
 fn get_search_paths(cli: &Rscli) -> Vec<PathBuf> {
-    if let Some(path_flag) = cli.find_user_flag_by_id(PATH_FLAG) {
-        path_flag.values.iter().map(PathBuf::from).collect()
+    if cli.find_user_flag_by_id(PATH_FLAG).is_some() {
+        cli.get_paths(PATH_FLAG)
     } else {
         vec![PathBuf::from(".")]
     }
 }
 
-fn search_in_path(path: &Path, cli: &Rscli, follow_symlink: bool) {
-    let walker = WalkDir::new(path)
-        .follow_links(follow_symlink)
+fn search_in_path(path: &Path, cli: &Rscli, matcher: &Matcher, follow_symlink: bool, status: &mut Status, out: &mut Output) {
+    let mut walker = WalkDir::new(path).follow_links(follow_symlink);
+    if let Some(max_depth) = cli.get_usize(MAX_DEPTH_FLAG) {
+        walker = walker.max_depth(max_depth);
+    }
+    let walker = walker
         .into_iter()
         .filter_entry(|e| filter(e, cli));
 
     for entry in walker {
-        if let Ok(e) = entry {
-            process_entry(&e, cli);
+        match entry {
+            Ok(e) => process_entry(&e, cli, matcher, status, out),
+            Err(err) => status.report(Error::from_walk(err, path)),
         }
     }
 }
 
-fn process_entry(entry: &DirEntry, cli: &Rscli) {
+fn process_entry(entry: &DirEntry, cli: &Rscli, matcher: &Matcher, status: &mut Status, out: &mut Output) {
     match entry.path().canonicalize() {
-        Ok(absolute) => {       
+        Ok(absolute) => {
             if absolute.is_file() {
-                match_file(&absolute, cli);
+                match_file(&absolute, cli, matcher, status, out);
             }
         }
-        Err(_) => return,
+        Err(err) => status.report(Error::Io { path: entry.path().to_path_buf(), source: err }),
     }
 }
 
-fn match_file(file_path: &Path, cli: &Rscli) {
-    let regex = cli.find_user_flag_by_id(REGEX_FLAG);
-    let patterns = &cli.free_args;
+fn match_file(file_path: &Path, cli: &Rscli, matcher: &Matcher, status: &mut Status, out: &mut Output) {
+    let bytes = match fs::read(file_path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            status.report(Error::Io { path: file_path.to_path_buf(), source: err });
+            return;
+        }
+    };
+    // Files that are not valid UTF-8 are treated as binary and skipped
+    let Ok(content) = String::from_utf8(bytes) else {
+        return;
+    };
 
-    match fs::read_to_string(file_path) {
-        Ok(content) => {
-            let mut line_matches: Vec<LineMatch> = vec![];
-            for (idx, line) in content.lines().enumerate() {
-                if let Some(regex) = regex {
-                    if let Some(match_line) = match_regex(line, idx, &regex.values) {
-                        line_matches.push(match_line);
-                    }
-                } else {
-                    if let Some(match_line) = match_pattern(line,idx, patterns) {
-                        line_matches.push(match_line);
-                    }
-                }
-            }
-            print_fmt(Match {
-                path: file_path.to_path_buf(),
-                lines: line_matches,
-            }, cli);
+    let lines = lines_with_offsets(&content);
+    let mut line_matches: Vec<LineMatch> = vec![];
+    for (idx, &(offset, line)) in lines.iter().enumerate() {
+        if let Some(match_line) = match_regex(line, idx, offset, matcher) {
+            line_matches.push(match_line);
         }
-        Err(_) => return,
+    }
+    if let Some((before, after)) = get_context(cli) {
+        line_matches = add_context(line_matches, &lines, before, after);
+    }
+
+    let follows_output = status.has_matched();
+    let selected = print_fmt(Match {
+        path: file_path.to_path_buf(),
+        lines: line_matches,
+    }, cli, follows_output, out);
+    if selected {
+        status.set_matched();
     }
 }
 
-fn match_regex(line: &str, line_number: usize, regex: &Vec<String>) -> Option<LineMatch> {
-    for reg in regex {
-        if let Ok(re) = Regex::new(reg){
-            if let Some(res) = re.find(line) {
-                return Some(LineMatch {
-                    line: line.to_string(),
-                    line_number: line_number,
-                    start: res.start(),
-                    end: res.end()
-                })
-            }
-        } else {
-            eprintln!("[ERROR] Failed to compile regex: {reg}");
+/// The lines of `content` as split by `str::lines`, with the byte offset
+/// where each starts.
+fn lines_with_offsets(content: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            let line = match line.strip_suffix('\n') {
+                Some(line) => line.strip_suffix('\r').unwrap_or(line),
+                None => line,
+            };
+            (start, line)
+        })
+        .collect()
+}
+
+/// Lines of context before and after matches, if any context flag is
+/// given. -A and -B take precedence over -C, as in grep.
+fn get_context(cli: &Rscli) -> Option<(usize, usize)> {
+    let context = cli.get_usize(CONTEXT_FLAG);
+    let before = cli.get_usize(BEFORE_CONTEXT_FLAG);
+    let after = cli.get_usize(AFTER_CONTEXT_FLAG);
+    if context.is_none() && before.is_none() && after.is_none() {
+        return None;
+    }
+    Some((before.or(context).unwrap_or(0), after.or(context).unwrap_or(0)))
+}
+
+/// Adds the lines around each match, in order and without repeating lines
+/// where the windows of close matches overlap.
+fn add_context(matches: Vec<LineMatch>, lines: &[(usize, &str)], before: usize, after: usize) -> Vec<LineMatch> {
+    let context_line = |line_number: usize| LineMatch {
+        line: lines[line_number].1.to_string(),
+        line_number,
+        byte_offset: lines[line_number].0,
+        spans: vec![],
+        context: true,
+    };
+
+    let mut with_context = vec![];
+    // First line not printed yet
+    let mut next = 0;
+    let mut matches = matches.into_iter().peekable();
+    while let Some(line_match) = matches.next() {
+        let idx = line_match.line_number;
+        for line_number in idx.saturating_sub(before).max(next)..idx {
+            with_context.push(context_line(line_number));
+        }
+        with_context.push(line_match);
+
+        let next_match = matches.peek().map_or(lines.len(), |m| m.line_number);
+        let end = idx.saturating_add(after).min(next_match - 1);
+        for line_number in idx + 1..=end {
+            with_context.push(context_line(line_number));
         }
+        next = end + 1;
     }
-    return None;
+    with_context
 }
 
-fn match_pattern(line: &str, line_number: usize, patterns: &Vec<String>) -> Option<LineMatch> {
-    for pattern in patterns {
-        if let Some(start) = line.find(pattern) {
-            return Some(LineMatch {
-                line: line.to_string(),
-                line_number: line_number,
-                start: start,
-                end: start + pattern.len()
-            })
+fn match_regex(line: &str, line_number: usize, byte_offset: usize, matcher: &Matcher) -> Option<LineMatch> {
+    let matched = matcher.set.matches(line);
+    if !matched.matched_any() {
+        return None;
+    }
+
+    let mut spans = vec![];
+    for index in matched.iter() {
+        for res in matcher.regexes[index].find_iter(line) {
+            if !res.is_empty() {
+                spans.push((res.start(), res.end()));
+            }
         }
     }
+    Some(line_match(line, line_number, byte_offset, spans))
+}
+
+fn line_match(line: &str, line_number: usize, byte_offset: usize, spans: Vec<(usize, usize)>) -> LineMatch {
+    LineMatch {
+        line: line.to_string(),
+        line_number,
+        byte_offset,
+        spans: merge_spans(spans),
+        context: false,
+    }
+}
 
-    return None;
+/// Sorts the spans found by all patterns and merges those that overlap, so
+/// each part of the line is highlighted once.
+fn merge_spans(mut spans: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    spans.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start < last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
 }
 
-fn print_fmt(matching: Match, cli: &Rscli) {
+/// Prints the match as requested by the flags and returns whether the file
+/// was selected, i.e. something got printed for it. `follows_output` tells
+/// whether earlier files printed lines, for the `--` between context groups.
+fn print_fmt(matching: Match, cli: &Rscli, follows_output: bool, out: &mut Output) -> bool {
     let line_number = cli.find_user_flag_by_id(LINE_NUMBER_FLAG);
     let matching_files = cli.find_user_flag_by_id(MATCH_ONLY_FLAG);
     let no_matching_files = cli.find_user_flag_by_id(NO_MATCH_ONLY_FLAG);
 
     
-    if no_matching_files.is_some() && matching.lines.len() == 0 {
-        print_path(&matching.path);
-        println!();
-        return;
-    } else if matching_files.is_some() && matching.lines.len() > 0 {
-        print_path(&matching.path);
-        println!();
-        return;
-    } else if line_number.is_some() {
-        for line in matching.lines {
-            print_path(&matching.path);
-            print_column();
-            print_line_number(line.line_number + 1);
-            print_column();
-            print_line(line);
-            println!();
+    if (no_matching_files.is_some() && matching.lines.is_empty())
+        || (matching_files.is_some() && !matching.lines.is_empty()) {
+        os::print_path(&matching.path, out);
+        out.newline();
+        return true;
+    }
+
+    let selected = !matching.lines.is_empty();
+    if no_matching_files.is_some() || matching_files.is_some() {
+        return false;
+    }
+
+    let vimgrep = cli.find_user_flag_by_id(VIMGREP_FLAG).is_some();
+    let only_matching = cli.find_user_flag_by_id(ONLY_MATCHING_FLAG).is_some();
+    let fields = Fields {
+        line_number: line_number.is_some() || vimgrep,
+        column: cli.find_user_flag_by_id(COLUMN_FLAG).is_some() || vimgrep,
+        byte_offset: cli.find_user_flag_by_id(BYTE_OFFSET_FLAG).is_some(),
+    };
+    let grouped = get_context(cli).is_some();
+    let mut previous = None;
+    for line in matching.lines {
+        // One output line per match, where context has no place
+        if only_matching || vimgrep {
+            if !line.context {
+                print_each_match(&matching.path, &line, &fields, only_matching, out);
+            }
+            continue;
         }
-    } else if no_matching_files.is_none() && matching_files.is_none(){
-        for line in matching.lines {
-            print_path(&matching.path);
-            print_column();
-            print_line(line);
-            println!();
+
+        // Groups of context that do not touch are separated, also across files
+        let separated = match previous {
+            Some(previous) => line.line_number != previous + 1,
+            None => follows_output,
+        };
+        if grouped && separated {
+            print_group_separator(out);
+            out.newline();
         }
+        previous = Some(line.line_number);
+
+        let start = line.spans.first().map_or(0, |&(start, _)| start);
+        print_prefix(&matching.path, &line, &fields, start, line.byte_offset, out);
+        print_line(&line.line, &line.spans, out);
+        out.newline();
     }
+    selected
 }
 
-fn print_path(path: &PathBuf) {
-    let mut path_str = path.to_string_lossy().to_string();
-    if path_str.starts_with(r"\\?\") {
-        path_str = path_str[4..].to_string();
+/// Which fields come between the path and the text.
+struct Fields {
+    line_number: bool,
+    column: bool,
+    byte_offset: bool,
+}
+
+/// For -o and --vimgrep. A line matched by an empty pattern still gets one
+/// output line with --vimgrep, at its start.
+fn print_each_match(path: &Path, line: &LineMatch, fields: &Fields, only_matching: bool, out: &mut Output) {
+    let whole_line = [(0, 0)];
+    let spans = if line.spans.is_empty() && !only_matching { &whole_line[..] } else { &line.spans[..] };
+    for &(start, end) in spans {
+        print_prefix(path, line, fields, start, line.byte_offset + start, out);
+        if only_matching {
+            print_line(&line.line[start..end], &[(0, end - start)], out);
+        } else {
+            print_line(&line.line, &line.spans, out);
+        }
+        out.newline();
     }
-    
-    let style = Colour::Purple.bold();
-    print!("{}", style.paint(path_str));
 }
 
-fn print_column() {
-    let style = Colour::Cyan.bold();
-    print!("{}", style.paint(":"));
+/// Prints `path:line:column:offset:`, each field only if asked for. `start`
+/// is the byte index in the line the column is computed from.
+fn print_prefix(path: &Path, line: &LineMatch, fields: &Fields, start: usize, byte_offset: usize, out: &mut Output) {
+    os::print_path(path, out);
+    print_separator(line.context, out);
+    if fields.line_number {
+        print_line_number(line.line_number + 1, out);
+        print_separator(line.context, out);
+    }
+    if fields.column {
+        print_line_number(start + 1, out);
+        print_separator(line.context, out);
+    }
+    if fields.byte_offset {
+        print_line_number(byte_offset, out);
+        print_separator(line.context, out);
+    }
 }
 
-fn print_line_number(line_number: usize) {
-    let style = Colour::Green.bold();
-    print!("{}", style.paint(line_number.to_string()));
+/// `:` after the path and line number of matching lines, `-` for context.
+fn print_separator(context: bool, out: &mut Output) {
+    paint(Colour::Cyan.bold(), if context { "-" } else { ":" }, out);
 }
 
-fn print_line(line: LineMatch) {
+fn print_group_separator(out: &mut Output) {
+    paint(Colour::Cyan.bold(), "--", out);
+}
+
+fn print_line_number(line_number: usize, out: &mut Output) {
+    paint(Colour::Green.bold(), &line_number.to_string(), out);
+}
+
+fn print_line(line: &str, spans: &[(usize, usize)], out: &mut Output) {
     let style = Colour::Red.bold();
-    print!("{}", &line.line[0..line.start]);
-    print!("{}", style.paint(&line.line[line.start..line.end]));
-    print!("{}", &line.line[line.end..]);
-}
\ No newline at end of file
+    let mut printed = 0;
+    for &(start, end) in spans {
+        out.write_str(&line[printed..start]);
+        paint(style, &line[start..end], out);
+        printed = end;
+    }
+    out.write_str(&line[printed..]);
+}
+
+fn paint(style: Style, text: &str, out: &mut Output) {
+    if out.colours() {
+        write!(out, "{}", style.paint(text));
+    } else {
+        out.write_str(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+    fn lines() -> Vec<(usize, &'static str)> {
+        LINES.iter().enumerate().map(|(i, &line)| (i * 2, line)).collect()
+    }
+
+    fn matched(line_number: usize) -> LineMatch {
+        line_match(LINES[line_number], line_number, line_number * 2, vec![(0, 1)])
+    }
+
+    /// The output as line numbers followed by `:` for matches and `-` for
+    /// context, like grep prints them.
+    fn context(matches: &[usize], before: usize, after: usize) -> Vec<String> {
+        let matches = matches.iter().map(|&n| matched(n)).collect();
+        add_context(matches, &lines(), before, after)
+            .iter()
+            .map(|m| format!("{}{}", m.line_number, if m.context { '-' } else { ':' }))
+            .collect()
+    }
+
+    #[test]
+    fn context_around_matches() {
+        assert_eq!(context(&[3], 1, 2), ["2-", "3:", "4-", "5-"]);
+        assert_eq!(context(&[3], 0, 0), ["3:"]);
+    }
+
+    #[test]
+    fn context_at_file_edges() {
+        assert_eq!(context(&[0], 3, 0), ["0:"]);
+        assert_eq!(context(&[7], 0, 3), ["7:"]);
+        assert_eq!(context(&[1, 6], 3, 3), ["0-", "1:", "2-", "3-", "4-", "5-", "6:", "7-"]);
+    }
+
+    #[test]
+    fn huge_context() {
+        assert_eq!(context(&[6], usize::MAX, usize::MAX), ["0-", "1-", "2-", "3-", "4-", "5-", "6:", "7-"]);
+    }
+
+    #[test]
+    fn overlapping_context_is_not_repeated() {
+        assert_eq!(context(&[2, 4], 1, 1), ["1-", "2:", "3-", "4:", "5-"]);
+        assert_eq!(context(&[2, 3], 2, 2), ["0-", "1-", "2:", "3:", "4-", "5-"]);
+    }
+
+    #[test]
+    fn context_lines_keep_their_offsets() {
+        let with_context = add_context(vec![matched(3)], &lines(), 1, 0);
+        assert_eq!(with_context[0].byte_offset, 4);
+        assert_eq!(with_context[0].line, "c");
+        assert!(with_context[0].spans.is_empty());
+    }
+
+    #[test]
+    fn overlapping_spans_merge() {
+        assert_eq!(merge_spans(vec![(0, 3), (2, 5)]), [(0, 5)]);
+        assert_eq!(merge_spans(vec![(0, 6), (2, 4)]), [(0, 6)]);
+        assert_eq!(merge_spans(vec![(4, 6), (0, 2), (1, 5)]), [(0, 6)]);
+    }
+
+    #[test]
+    fn touching_spans_stay_apart() {
+        // `-o` prints "foo" and "bar" of "foobar" on their own lines
+        assert_eq!(merge_spans(vec![(3, 6), (0, 3)]), [(0, 3), (3, 6)]);
+        assert_eq!(merge_spans(vec![(0, 1), (2, 3)]), [(0, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn duplicate_spans() {
+        assert_eq!(merge_spans(vec![(1, 2), (1, 2)]), [(1, 2)]);
+        assert_eq!(merge_spans(vec![]), []);
+    }
+
+    fn matches(matcher: &Matcher, line: &str) -> bool {
+        match_regex(line, 0, 0, matcher).is_some()
+    }
+
+    #[test]
+    fn regexes_and_fixed_strings_together() {
+        let matcher = Matcher::new(&["fo+"], &["b.r"], MatchOptions::default()).unwrap();
+        assert!(matches(&matcher, "xfooo"));
+        assert!(matches(&matcher, "a b.r"));
+        assert!(!matches(&matcher, "bar"));
+    }
+
+    fn options(ignore_case: bool, smart_case: bool, word: bool, line: bool) -> MatchOptions {
+        MatchOptions {
+            ignore_case,
+            smart_case,
+            word,
+            line,
+        }
+    }
+
+    fn fixed(pattern: &str, options: MatchOptions) -> Matcher {
+        Matcher::new(&[], &[pattern], options).unwrap()
+    }
+
+    #[test]
+    fn uppercase_in_patterns() {
+        assert!(has_uppercase("Err", true));
+        assert!(!has_uppercase("err", true));
+        assert!(has_uppercase(r"\W", true));
+        assert!(!has_uppercase(r"\W\p{Lu}\PL\S", false));
+        assert!(has_uppercase(r"\wA", false));
+        assert!(has_uppercase("É", true));
+    }
+
+    #[test]
+    fn regex_wrapping() {
+        assert_eq!(to_regex("a.b", true, MatchOptions::default()), r"a\.b");
+        assert_eq!(to_regex("a|b", false, options(false, false, false, true)), "^(?:a|b)$");
+        // -x already covers the whole line, -w adds nothing
+        assert_eq!(to_regex("a", false, options(true, false, true, true)), "(?i)^(?:a)$");
+        assert_eq!(to_regex("A", false, options(false, true, false, false)), "A");
+        assert_eq!(to_regex("a", false, options(false, true, false, false)), "(?i)a");
+    }
+
+    #[test]
+    fn fixed_strings_are_escaped() {
+        let matcher = fixed("foo(bar)", MatchOptions::default());
+        assert!(matches(&matcher, "x foo(bar) y"));
+        assert!(!matches(&matcher, "foobar"));
+    }
+
+    #[test]
+    fn whole_words() {
+        let matcher = fixed("foo(bar)", options(false, false, true, false));
+        assert!(matches(&matcher, "call foo(bar);"));
+        assert!(!matches(&matcher, "xfoo(bar)"));
+
+        let matcher = fixed("foo", options(false, false, true, false));
+        assert!(matches(&matcher, "foo-bar"));
+        assert!(!matches(&matcher, "foobar"));
+        assert!(!matches(&matcher, "_foo"));
+    }
+
+    #[test]
+    fn whole_lines_ignoring_case() {
+        let matcher = fixed("ÉCOLE", options(true, false, false, true));
+        assert!(matches(&matcher, "école"));
+        assert!(!matches(&matcher, "écoles"));
+    }
+
+    #[test]
+    fn smart_case() {
+        let matcher = fixed("error", options(false, true, false, false));
+        assert!(matches(&matcher, "ERROR"));
+        let matcher = fixed("Error", options(false, true, false, false));
+        assert!(!matches(&matcher, "ERROR"));
+        assert!(matches(&matcher, "Error"));
+    }
+
+    #[test]
+    fn highlight_spans_ignoring_case() {
+        let matcher = fixed("σίσυφος", options(true, false, false, false));
+        let line = match_regex("Σίσυφος και σίσυφος", 0, 0, &matcher).unwrap();
+        assert_eq!(line.spans, [(0, 14), (22, 36)]);
+    }
+}
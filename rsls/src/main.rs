@@ -1,5 +1,14 @@
+use std::env;
+use std::ffi::OsString;
 use std::fs::{self, Metadata, DirEntry};
 use ansi_term::Colour;
+use std::path::{Path, PathBuf};
+use winux_cli::error::{Error, Status};
+use winux_cli::output::Output;
+use winux_cli::rscli::Rscli;
+
+const ABOUT: &str = "List the content of a directory.";
+const SYNOPSIS: &str = "[OPTIONS] [DIR]";
 
 const SECONDS_IN_MINUTE: u64 = 60;
 const SECONDS_IN_HOUR: u64 = 3600;
@@ -10,92 +19,119 @@ const CEST_UTC_ITALY: u64 = 2;
 const _CET_UTC_ITALY: u64 = 1;
 
 fn main() {
-    let path: String;
-    if let Some(arg) = std::env::args().nth(1) {
-        path = arg;
-    } else {
-        path = ".".to_string();
-    }
-    if let Ok(entries) = fs::read_dir(path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Ok(metadata) = entry.metadata() {
-                    if let Some(filename) = entry.file_name().to_str() { 
-                        print_type(&metadata);
-                        print_permissions(&metadata);
-                        // print_size(&metadata);
-                        print_last_modified(&metadata);
-                        print_filename(&metadata, &entry, filename);
-                        println!();
-                    }
-                } else {
-                    println!("Couldn't get metadata for {:?}", entry.path());
+    let args: Vec<OsString> = env::args_os().collect();
+
+    let program_name = args.first().expect("[ERROR] Program name should exist.").to_string_lossy().into_owned();
+    let program_flags = vec![];
+    let examples = vec![
+        format!("{program_name}  // List the current directory"),
+        format!("{program_name} <dir>  // List the content of dir"),
+        ];
+    let mut cli = Rscli::new(program_name, ABOUT, SYNOPSIS, program_flags, examples);
+    cli.parse_args(&args);
+
+    let path = cli.free_args.first().map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let mut out = Output::new();
+    let status = list(&path, &mut out);
+    out.flush();
+    status.exit();
+}
+
+fn list(path: &Path, out: &mut Output) -> Status {
+    let mut status = Status::new();
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            status.report(Error::Io { path: path.to_path_buf(), source: err });
+            return status;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                status.report(Error::Io { path: path.to_path_buf(), source: err });
+                continue;
+            }
+        };
+        match entry.metadata() {
+            Ok(metadata) => {
+                if let Some(filename) = entry.file_name().to_str() { 
+                    print_type(&metadata, out);
+                    print_permissions(&metadata, out);
+                    // print_size(&metadata, out);
+                    print_last_modified(&metadata, out);
+                    print_filename(&metadata, &entry, filename, out);
+                    out.newline();
                 }
             }
+            Err(err) => status.report(Error::Io { path: entry.path(), source: err }),
         }
     }
+    status
 }
 
-fn print_type(metadata: &Metadata) {
+fn print_type(metadata: &Metadata, out: &mut Output) {
     
     if metadata.is_dir() {
         let style = Colour::Blue.bold();
-        print!("{}", style.paint("d")); 
+        write!(out, "{}", style.paint("d")); 
     } else if metadata.is_file() {
-        print!("{}", "-");
+        write!(out, "-");
     } else {
         let style = Colour::Cyan.bold();
-        print!("{}", style.paint("l")); 
+        write!(out, "{}", style.paint("l")); 
     }
 }
 
-fn print_permissions(metadata: &Metadata)  {
+fn print_permissions(metadata: &Metadata, out: &mut Output)  {
     let yellow = Colour::Yellow.bold();
     let red = Colour::Red.bold();
     
     if metadata.permissions().readonly() {
-        print!("{}{}", yellow.paint("r"), red.paint("-"));
+        write!(out, "{}{}", yellow.paint("r"), red.paint("-"));
     } else {
-        print!("{}{}", yellow.paint("r"), red.paint("w"));
+        write!(out, "{}{}", yellow.paint("r"), red.paint("w"));
     }
 }
 
-fn _print_size(metadata: &Metadata) {
+fn _print_size(metadata: &Metadata, out: &mut Output) {
     let size = metadata.len();
     let style = Colour::Green.bold();
     if metadata.is_dir() {
-        print!("{:<10}", style.paint("-"));
+        write!(out, "{:<10}", style.paint("-"));
     } else if size > 1024 * 1024 {
-        print!("{:<10}", style.paint(format!("{}M", size/1024/1024)));
+        write!(out, "{:<10}", style.paint(format!("{}M", size/1024/1024)));
     } else {
-        print!("{:<10}", style.paint(format!("{}", size)));
+        write!(out, "{:<10}", style.paint(format!("{}", size)));
     }
 } 
 
 
-fn print_last_modified(metadata: &Metadata)  {
+fn print_last_modified(metadata: &Metadata, out: &mut Output)  {
     if let Ok(time) = metadata.modified() {
         if let Ok(duration) = time.duration_since(std::time::UNIX_EPOCH) {
             let datetime: String = convert_epoch_to_datetime(duration.as_secs(), CEST_UTC_ITALY);
             let style = Colour::Blue.bold();
-            print!("{:<10}{}{:>10}","", style.paint(datetime), "");
+            write!(out, "{:<10}{}{:>10}","", style.paint(datetime), "");
         } 
     }
 }
 
-fn print_filename(metadata: &Metadata, entry: &DirEntry, filename: &str) {
+fn print_filename(metadata: &Metadata, entry: &DirEntry, filename: &str, out: &mut Output) {
     
     if metadata.is_dir() {
         let style = Colour::Blue.bold();
-        print!("{}", style.paint(filename));
+        write!(out, "{}", style.paint(filename));
     } else if metadata.is_file() {
-        print!("{}", filename);
+        write!(out, "{}", filename);
     } else {
         let style = Colour::Cyan.bold();
         if let Ok(link) = fs::read_link(entry.path()) {
-            print!("{} -> {}", style.paint(filename), link.display());
+            write!(out, "{} -> {}", style.paint(filename), link.display());
         } else {
-            print!("{} -> can't find linked file", style.paint(filename));
+            write!(out, "{} -> can't find linked file", style.paint(filename));
         }
     }
 }
@@ -0,0 +1,151 @@
+use std::fmt::Write;
+use crate::rscli::{Arity, Flag, Rscli, ValueKind};
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn from_name(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a completion script for `shell` from the flags declared in `cli`.
+pub fn generate(cli: &Rscli, shell: Shell) -> String {
+    let bin = cli.bin_name();
+    let flags: Vec<&Flag> = cli.visible_flags().collect();
+    match shell {
+        Shell::Bash => bash(&bin, &flags),
+        Shell::Zsh => zsh(&bin, &flags),
+        Shell::Fish => fish(&bin, &flags),
+    }
+}
+
+fn spellings(flag: &Flag) -> Vec<String> {
+    let mut spellings = vec![];
+    if let Some(short) = flag.short() {
+        spellings.push(format!("-{short}"));
+    }
+    if let Some(long) = flag.long {
+        spellings.push(format!("--{long}"));
+    }
+    spellings
+}
+
+fn bash(bin: &str, flags: &[&Flag]) -> String {
+    let func = format!("_{}", bin.replace('-', "_"));
+    let words: Vec<String> = flags.iter().flat_map(|f| spellings(f)).collect();
+
+    let mut script = String::new();
+    let _ = writeln!(script, "{func}() {{");
+    let _ = writeln!(script, "    local cur prev");
+    let _ = writeln!(script, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"");
+    let _ = writeln!(script, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "    case \"$prev\" in");
+    for flag in flags.iter().filter(|f| f.arity != Arity::None) {
+        let reply = match flag.kind {
+            ValueKind::Path => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_owned(),
+            ValueKind::Choice(choices) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", choices.join(" ")),
+            _ => "COMPREPLY=()".to_owned(),
+        };
+        let _ = writeln!(script, "        {})", spellings(flag).join("|"));
+        let _ = writeln!(script, "            {reply}");
+        let _ = writeln!(script, "            return");
+        let _ = writeln!(script, "            ;;");
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "    if [[ \"$cur\" == -* ]]; then");
+    let _ = writeln!(script, "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", words.join(" "));
+    let _ = writeln!(script, "        return");
+    let _ = writeln!(script, "    fi");
+    let _ = writeln!(script, "    COMPREPLY=($(compgen -f -- \"$cur\"))");
+    let _ = writeln!(script, "}}");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "complete -o filenames -F {func} {bin}");
+    script
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh(bin: &str, flags: &[&Flag]) -> String {
+    let func = format!("_{}", bin.replace('-', "_"));
+
+    let mut script = String::new();
+    let _ = writeln!(script, "#compdef {bin}");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "{func}() {{");
+    let _ = writeln!(script, "    _arguments -s \\");
+    for flag in flags {
+        let description = zsh_escape(flag.description);
+        let action = match flag.kind {
+            ValueKind::Path => ":path:_files".to_owned(),
+            ValueKind::Choice(choices) => format!(":value:({})", choices.join(" ")),
+            _ => ":value: ".to_owned(),
+        };
+        let (repeat, short_suffix, long_suffix, action) = match flag.arity {
+            Arity::None => ("", "", "", String::new()),
+            Arity::One | Arity::Many => ("*", "+", "=", action),
+        };
+        let mut names = vec![];
+        if let Some(short) = flag.short() {
+            names.push(format!("-{short}{short_suffix}"));
+        }
+        if let Some(long) = flag.long {
+            names.push(format!("--{long}{long_suffix}"));
+        }
+        let names = if names.len() == 1 {
+            names[0].clone()
+        } else {
+            format!("{{{}}}", names.join(","))
+        };
+        let _ = writeln!(script, "        '{repeat}'{names}'[{description}]{action}' \\");
+    }
+    let _ = writeln!(script, "        '*:file:_files'");
+    let _ = writeln!(script, "}}");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "{func} \"$@\"");
+    script
+}
+
+fn fish(bin: &str, flags: &[&Flag]) -> String {
+    let mut script = String::new();
+    for flag in flags {
+        let mut line = format!("complete -c {bin}");
+        if let Some(short) = flag.short() {
+            let _ = write!(line, " -s {short}");
+        }
+        if let Some(long) = flag.long {
+            let _ = write!(line, " -l {long}");
+        }
+        if flag.arity != Arity::None {
+            match flag.kind {
+                ValueKind::Path => line.push_str(" -r -F"),
+                ValueKind::Choice(choices) => {
+                    let _ = write!(line, " -x -a '{}'", choices.join(" "));
+                }
+                _ => line.push_str(" -x"),
+            }
+        }
+        let _ = write!(line, " -d '{}'", flag.description.replace('\'', "\\'"));
+        let _ = writeln!(script, "{line}");
+    }
+    script
+}
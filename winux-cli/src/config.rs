@@ -0,0 +1,174 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml::{Table, Value};
+use crate::error::Error;
+use crate::rscli::{self, Flag};
+
+/// Overrides the location of the config file, like `RIPGREP_CONFIG_PATH`.
+pub const CONFIG_PATH_VAR: &str = "WINUX_CONFIG_PATH";
+
+/// Where the config file lives when `WINUX_CONFIG_PATH` is not set:
+/// `$XDG_CONFIG_HOME/winux/config.toml`, falling back to `~/.config` on Unix
+/// and `%APPDATA%` on Windows.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_PATH_VAR) {
+        return Some(PathBuf::from(path));
+    }
+
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+    Some(base.join("winux").join("config.toml"))
+}
+
+/// The environment variable holding extra flags for `bin`, e.g. `RSGREP_OPTS`.
+pub fn opts_var(bin: &str) -> String {
+    format!("{}_OPTS", bin.to_uppercase().replace('-', "_"))
+}
+
+/// Arguments from one place other than the command line.
+pub struct Defaults {
+    /// The config file or variable they come from, for error messages
+    pub source: String,
+    pub args: Vec<String>,
+}
+
+/// Default arguments for `bin`: first the `[bin]` section of the config file,
+/// then `<BIN>_OPTS`. They are meant to be parsed before the real command
+/// line so that the latter wins.
+pub fn default_args(bin: &str, flags: &[Flag]) -> Result<Vec<Defaults>, Error> {
+    let mut defaults = vec![];
+    if let Some(path) = config_path() {
+        defaults.push(Defaults {
+            args: read_config(&path, bin, flags)?,
+            source: path.display().to_string(),
+        });
+    }
+
+    let var = opts_var(bin);
+    if let Ok(opts) = env::var(&var) {
+        defaults.push(Defaults {
+            args: split_words(&opts).map_err(|msg| Error::Config(format!("{var}: {msg}")))?,
+            source: var,
+        });
+    }
+    Ok(defaults)
+}
+
+/// Turns the `[bin]` section into arguments. Keys are long flag names:
+/// `true` enables a switch, any other value is passed as the flag value and
+/// arrays repeat the flag.
+///
+/// ```toml
+/// [rsgrep]
+/// line-number = true
+/// exclude = ["target", ".git"]
+/// ```
+fn read_config(path: &PathBuf, bin: &str, flags: &[Flag]) -> Result<Vec<String>, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        // Having no config file is the common case
+        Err(err) if err.kind() == io::ErrorKind::NotFound && env::var_os(CONFIG_PATH_VAR).is_none() => {
+            return Ok(vec![]);
+        }
+        Err(err) => return Err(Error::Io { path: path.clone(), source: err }),
+    };
+
+    let config_error = |msg: String| Error::Config(format!("{}: {msg}", path.display()));
+    let table: Table = content.parse().map_err(|err: toml::de::Error| config_error(err.message().to_owned()))?;
+    let Some(section) = table.get(bin) else {
+        return Ok(vec![]);
+    };
+    let Some(section) = section.as_table() else {
+        return Err(config_error(format!("[{bin}] must be a table")));
+    };
+
+    let mut args = vec![];
+    for (key, value) in section {
+        let Some(flag) = flags.iter().find(|f| f.long == Some(key.as_str()) || f.id == key) else {
+            return Err(config_error(format!("unknown option '{key}' in [{bin}]")));
+        };
+        // They would print help or generate files on every run
+        if rscli::is_builtin(flag) {
+            return Err(config_error(format!("'{key}' cannot be set in [{bin}]")));
+        }
+        let name = match flag.long {
+            Some(long) => format!("--{long}"),
+            None => format!("-{}", flag.id),
+        };
+
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(name.clone()),
+                Value::Boolean(false) => (),
+                Value::String(s) => args.push(format!("{name}={s}")),
+                Value::Integer(_) | Value::Float(_) | Value::Datetime(_) => args.push(format!("{name}={value}")),
+                Value::Array(_) | Value::Table(_) => {
+                    return Err(config_error(format!("unsupported value for '{key}' in [{bin}]")));
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Splits `<BIN>_OPTS` on whitespace, keeping single or double quoted words together.
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_owned());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words() {
+        assert_eq!(split_words("  -n   --context=2 ").unwrap(), ["-n", "--context=2"]);
+        assert_eq!(split_words("").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn quoted_words() {
+        assert_eq!(split_words(r#"-e "my dir" -p 'a "b"'"#).unwrap(), ["-e", "my dir", "-p", r#"a "b""#]);
+        assert_eq!(split_words(r#"--exclude="" x"#).unwrap(), ["--exclude=", "x"]);
+        assert_eq!(split_words("''").unwrap(), [""]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        assert!(split_words("-e 'abc").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// Something was found or the command succeeded.
+pub const EXIT_SUCCESS: i32 = 0;
+/// A search ran without errors but found nothing.
+pub const EXIT_NO_MATCH: i32 = 1;
+/// Bad usage or at least one error while running.
+pub const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be parsed: unknown flag, missing or invalid value.
+    Usage(String),
+    /// Reading or walking a specific path failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The config file or the `<TOOL>_OPTS` variable holds invalid defaults.
+    Config(String),
+}
+
+impl Error {
+    /// The error from walking the directory tree under `root`, on the entry
+    /// that failed when it is known.
+    pub fn from_walk(err: walkdir::Error, root: &Path) -> Error {
+        let path = err.path().unwrap_or(root).to_path_buf();
+        let message = err.to_string();
+        let source = err.into_io_error().unwrap_or_else(|| io::Error::other(message));
+        Error::Io { path, source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(msg) => write!(f, "{msg}"),
+            Error::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::Config(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Usage(_) | Error::Config(_) => None,
+            Error::Io { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Collects the outcome of a run so it can be turned into the exit status.
+/// Errors are printed as soon as they are reported so the tool can keep
+/// going with the remaining files.
+#[derive(Debug, Default)]
+pub struct Status {
+    matched: Option<bool>,
+    failed: bool,
+}
+
+impl Status {
+    /// For tools that either succeed or fail, like rscat and rsls.
+    pub fn new() -> Self {
+        Status::default()
+    }
+
+    /// For tools where finding nothing is not an error but still exits with 1.
+    pub fn for_search() -> Self {
+        Status {
+            matched: Some(false),
+            failed: false,
+        }
+    }
+
+    pub fn set_matched(&mut self) {
+        self.matched = Some(true);
+    }
+
+    pub fn has_matched(&self) -> bool {
+        self.matched == Some(true)
+    }
+
+    pub fn report(&mut self, err: Error) {
+        eprintln!("[ERROR] {err}");
+        self.failed = true;
+    }
+
+    pub fn code(&self) -> i32 {
+        if self.failed {
+            EXIT_ERROR
+        } else if self.matched == Some(false) {
+            EXIT_NO_MATCH
+        } else {
+            EXIT_SUCCESS
+        }
+    }
+
+    pub fn exit(&self) -> ! {
+        exit(self.code())
+    }
+}
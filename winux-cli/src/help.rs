@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use crate::error::{EXIT_ERROR, EXIT_NO_MATCH, EXIT_SUCCESS};
+use crate::rscli::Rscli;
+
+const EXIT_STATUSES: [(i32, &str); 3] = [
+    (EXIT_SUCCESS, "Success. For search tools, at least one match was found."),
+    (EXIT_NO_MATCH, "Search tools only: nothing matched."),
+    (EXIT_ERROR, "Bad usage, or an error occurred while reading a file or directory."),
+];
+
+/// Splits an example like `rsgrep 'foo' -rn  // What it does` into the
+/// command and its explanation.
+fn split_example(example: &str) -> (&str, Option<&str>) {
+    match example.split_once("//") {
+        Some((command, explanation)) => (command.trim(), Some(explanation.trim())),
+        None => (example.trim(), None),
+    }
+}
+
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-").replace('"', "\\(dq");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{escaped}")
+    } else {
+        escaped
+    }
+}
+
+/// Renders a man page in roff format, ready for `man -l` or packaging.
+pub fn render_man(cli: &Rscli) -> String {
+    let bin = cli.bin_name();
+    let mut page = String::new();
+
+    let _ = writeln!(page, ".TH {} 1 \"\" \"winux-rs\" \"User Commands\"", bin.to_uppercase());
+    let _ = writeln!(page, ".SH NAME");
+    let _ = writeln!(page, "{} \\- {}", roff_escape(&bin), roff_escape(cli.about));
+
+    let _ = writeln!(page, ".SH SYNOPSIS");
+    let _ = writeln!(page, ".B {}", roff_escape(&bin));
+    let _ = writeln!(page, "{}", roff_escape(cli.synopsis));
+
+    let _ = writeln!(page, ".SH OPTIONS");
+    for flag in cli.visible_flags() {
+        let _ = writeln!(page, ".TP");
+        let _ = writeln!(page, "\\fB{}\\fR{}", roff_escape(flag.names().trim()), roff_escape(&flag.value_hint()));
+        let _ = writeln!(page, "{}", roff_escape(flag.description));
+    }
+
+    let _ = writeln!(page, ".SH EXAMPLES");
+    for example in cli.bin_examples() {
+        let (command, explanation) = split_example(&example);
+        let _ = writeln!(page, ".TP");
+        let _ = writeln!(page, ".B {}", roff_escape(command));
+        if let Some(explanation) = explanation {
+            let _ = writeln!(page, "{}", roff_escape(explanation));
+        }
+    }
+
+    let _ = writeln!(page, ".SH \"EXIT STATUS\"");
+    for (code, meaning) in EXIT_STATUSES {
+        let _ = writeln!(page, ".TP");
+        let _ = writeln!(page, ".B {code}");
+        let _ = writeln!(page, "{}", roff_escape(meaning));
+    }
+    page
+}
+
+/// Renders the same content as `render_man` as a Markdown reference.
+pub fn render_markdown(cli: &Rscli) -> String {
+    let bin = cli.bin_name();
+    let mut doc = String::new();
+
+    let _ = writeln!(doc, "# {bin}");
+    let _ = writeln!(doc, "{}", cli.about);
+    let _ = writeln!(doc);
+
+    let _ = writeln!(doc, "## Synopsis");
+    let _ = writeln!(doc, "```console");
+    let _ = writeln!(doc, "{bin} {}", cli.synopsis);
+    let _ = writeln!(doc, "```");
+    let _ = writeln!(doc);
+
+    let _ = writeln!(doc, "## Options");
+    for flag in cli.visible_flags() {
+        let _ = writeln!(doc, "- `{}{}`: {}", flag.names().trim(), flag.value_hint(), flag.description);
+    }
+    let _ = writeln!(doc);
+
+    let _ = writeln!(doc, "## Examples");
+    let _ = writeln!(doc, "```console");
+    for example in cli.bin_examples() {
+        match split_example(&example) {
+            (command, Some(explanation)) => {
+                let _ = writeln!(doc, "$ {command}  # {explanation}");
+            }
+            (command, None) => {
+                let _ = writeln!(doc, "$ {command}");
+            }
+        }
+    }
+    let _ = writeln!(doc, "```");
+    let _ = writeln!(doc);
+
+    let _ = writeln!(doc, "## Exit status");
+    for (code, meaning) in EXIT_STATUSES {
+        let _ = writeln!(doc, "- `{code}`: {meaning}");
+    }
+    doc
+}
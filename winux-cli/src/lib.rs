@@ -0,0 +1,7 @@
+pub mod completions;
+pub mod config;
+pub mod error;
+pub mod help;
+pub mod os;
+pub mod output;
+pub mod rscli;
@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::Path;
+use ansi_term::Colour;
+use crate::output::Output;
+
+/// Whether `needle` occurs in `haystack`. Works on the raw encoded bytes, so
+/// names that are not valid UTF-8 can still be matched instead of panicking.
+pub fn contains(haystack: &OsStr, needle: &OsStr) -> bool {
+    let haystack = haystack.as_encoded_bytes();
+    let needle = needle.as_encoded_bytes();
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// Bytes to print for `name`. Valid UTF-8 is printed as is. Otherwise a
+/// terminal gets the invalid bytes escaped as `\xNN`, while a pipe gets the
+/// raw bytes on Unix so the name can be passed back to other tools.
+pub fn printable(name: &OsStr, terminal: bool) -> Cow<'_, [u8]> {
+    let bytes = name.as_encoded_bytes();
+    if std::str::from_utf8(bytes).is_ok() {
+        return Cow::Borrowed(bytes);
+    }
+
+    if terminal {
+        let mut escaped = String::new();
+        for chunk in bytes.utf8_chunks() {
+            escaped.push_str(chunk.valid());
+            for byte in chunk.invalid() {
+                escaped.push_str(&format!("\\x{byte:02X}"));
+            }
+        }
+        Cow::Owned(escaped.into_bytes())
+    } else if cfg!(unix) {
+        Cow::Borrowed(bytes)
+    } else {
+        Cow::Owned(name.to_string_lossy().into_owned().into_bytes())
+    }
+}
+
+/// Prints `path` as given by `printable`, coloured when `out` uses colours.
+pub fn print_path(path: &Path, out: &mut Output) {
+    let name = printable(path.as_os_str(), out.is_terminal());
+    let name = name.strip_prefix(br"\\?\").unwrap_or(&name);
+
+    // Escapes would end up in the name when it is passed on to other tools
+    if !out.colours() {
+        out.write_bytes(name);
+        return;
+    }
+    let style = Colour::Purple.bold();
+    write!(out, "{}", style.prefix());
+    out.write_bytes(name);
+    write!(out, "{}", style.suffix());
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::{self, BufWriter, IsTerminal, StdoutLock, Write};
+use std::process::exit;
+use crate::error::EXIT_ERROR;
+
+/// Exit status of a process killed by SIGPIPE, what shells expect from
+/// `tool | head` once the reader goes away.
+pub const EXIT_BROKEN_PIPE: i32 = 128 + 13;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Buffered standard output shared by every tool. Writes never fail from the
+/// caller's point of view: a closed pipe ends the process quietly with
+/// `EXIT_BROKEN_PIPE`, any other error is reported and exits with `EXIT_ERROR`.
+///
+/// Output is flushed in large blocks, or at every newline when writing to a
+/// terminal so interactive use still sees results as they are found.
+pub struct Output {
+    out: BufWriter<Sink>,
+    terminal: bool,
+    colours: bool,
+}
+
+impl Output {
+    pub fn new() -> Self {
+        let stdout = io::stdout();
+        let terminal = stdout.is_terminal();
+        Output {
+            out: BufWriter::with_capacity(BUFFER_SIZE, Sink::Stdout(stdout.lock())),
+            terminal,
+            colours: terminal,
+        }
+    }
+
+    /// An output kept in memory, for tests to check what was written.
+    pub fn memory() -> Self {
+        Output {
+            out: BufWriter::new(Sink::Memory(vec![])),
+            terminal: false,
+            colours: false,
+        }
+    }
+
+    /// What was written to an output from `Output::memory` since the last call.
+    pub fn take_memory(&mut self) -> Vec<u8> {
+        self.flush();
+        match self.out.get_mut() {
+            Sink::Stdout(_) => vec![],
+            Sink::Memory(bytes) => std::mem::take(bytes),
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    /// Whether to write colour escapes, by default only to a terminal.
+    pub fn colours(&self) -> bool {
+        self.colours
+    }
+
+    pub fn set_colours(&mut self, colours: bool) {
+        self.colours = colours;
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        let result = self.out.write_all(bytes);
+        check(result);
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Lets `write!(out, ...)` be used like with `print!`.
+    pub fn write_fmt(&mut self, args: fmt::Arguments) {
+        let result = self.out.write_fmt(args);
+        check(result);
+    }
+
+    pub fn newline(&mut self) {
+        self.write_bytes(b"\n");
+        if self.terminal {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let result = self.out.flush();
+        check(result);
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::new()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+enum Sink {
+    Stdout(StdoutLock<'static>),
+    Memory(Vec<u8>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout(stdout) => stdout.write(buf),
+            Sink::Memory(bytes) => bytes.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Memory(_) => Ok(()),
+        }
+    }
+}
+
+fn check(result: io::Result<()>) {
+    match result {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => exit(EXIT_BROKEN_PIPE),
+        Err(err) => {
+            eprintln!("[ERROR] stdout: {err}");
+            exit(EXIT_ERROR);
+        }
+    }
+}
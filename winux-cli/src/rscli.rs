@@ -0,0 +1,721 @@
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+use crate::completions::{self, Shell};
+use crate::config;
+use crate::error::{Error, EXIT_ERROR, EXIT_SUCCESS};
+use crate::help;
+use crate::output::Output;
+
+const HELP_FLAG: &str = "h";
+const HELP_LONG: &str = "help";
+const HELP_DESC: &str = "Print this help and exit";
+const NO_CONFIG_FLAG: &str = "no-config";
+const NO_CONFIG_DESC: &str = "Ignore the config file and the <TOOL>_OPTS variable";
+const GENERATE_COMPLETIONS_FLAG: &str = "generate-completions";
+const GENERATE_COMPLETIONS_DESC: &str = "Print a completion script for the given shell";
+const GENERATE_MAN_FLAG: &str = "generate-man";
+const GENERATE_MAN_DESC: &str = "Print the man page in roff format";
+const GENERATE_MARKDOWN_FLAG: &str = "generate-markdown";
+const GENERATE_MARKDOWN_DESC: &str = "Print the reference documentation in Markdown";
+
+/// How many values a flag takes on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arity {
+    /// A switch: `-r`, `--recursive`.
+    #[default]
+    None,
+    /// Exactly one value per occurrence: `-p /home`, `-p/home`, `--path=/home`.
+    /// Repeating the flag collects one more value each time.
+    One,
+    /// One value like `One`, plus every following bare argument up to the
+    /// next flag.
+    Many,
+}
+
+/// What a flag value must look like. Values are checked while parsing so that
+/// bad input is rejected before the tool starts working.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueKind {
+    #[default]
+    Text,
+    /// A non negative integer: `3`.
+    Integer,
+    /// A byte count with an optional binary suffix: `512`, `10K`, `10M`, `1G`.
+    Size,
+    /// A time span with an optional unit, seconds by default: `30s`, `5m`, `7d`.
+    Duration,
+    /// One of a fixed set of words: `auto|always|never`.
+    Choice(&'static [&'static str]),
+    Path,
+}
+
+impl ValueKind {
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self {
+            ValueKind::Text => Ok(()),
+            ValueKind::Integer => parse_integer(value).map(|_| ()),
+            ValueKind::Size => parse_size(value).map(|_| ()),
+            ValueKind::Duration => parse_duration(value).map(|_| ()),
+            ValueKind::Choice(choices) => {
+                if choices.contains(&value) {
+                    Ok(())
+                } else {
+                    Err(format!("expected one of {}", choices.join(", ")))
+                }
+            }
+            ValueKind::Path => {
+                if value.is_empty() {
+                    Err("expected a path".to_owned())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_integer(value: &str) -> Result<usize, String> {
+    value.parse::<usize>().map_err(|_| "expected a non negative integer".to_owned())
+}
+
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let err = || "expected a size like 512, 10K, 10M or 1G".to_owned();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number: u64 = value[..digits].parse().map_err(|_| err())?;
+    let shift = match value[digits..].to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return Err(err()),
+    };
+    number.checked_mul(1 << shift).ok_or_else(err)
+}
+
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let err = || "expected a duration like 30s, 5m, 2h or 7d".to_owned();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let number: u64 = value[..digits].parse().map_err(|_| err())?;
+    let seconds = match &value[digits..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    number.checked_mul(seconds).map(Duration::from_secs).ok_or_else(err)
+}
+
+/// A command line option. `id` is the key used to look the flag up and,
+/// when it is a single character, also its short form (`-r`). `long` is the
+/// optional GNU-style name (`--recursive`).
+#[derive(Debug, Clone, Default)]
+pub struct Flag {
+    pub id: &'static str,
+    pub long: Option<&'static str>,
+    pub description: &'static str,
+    pub arity: Arity,
+    pub kind: ValueKind,
+    /// Hidden flags are parsed as usual but left out of the usage and the
+    /// generated completions.
+    pub hidden: bool,
+    pub values: Vec<OsString>,
+}
+
+impl Flag {
+    pub fn short(&self) -> Option<char> {
+        let mut chars = self.id.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Both spellings of the flag as shown in the usage, e.g. `-r, --recursive`.
+    pub fn names(&self) -> String {
+        match (self.short(), self.long) {
+            (Some(short), Some(long)) => format!("-{short}, --{long}"),
+            (Some(short), None) => format!("-{short}"),
+            (None, Some(long)) => format!("    --{long}"),
+            (None, None) => self.id.to_owned(),
+        }
+    }
+
+    pub fn value_hint(&self) -> String {
+        let hint = match self.kind {
+            ValueKind::Text => "VALUE".to_owned(),
+            ValueKind::Integer => "NUM".to_owned(),
+            ValueKind::Size => "SIZE".to_owned(),
+            ValueKind::Duration => "DURATION".to_owned(),
+            ValueKind::Choice(choices) => choices.join("|"),
+            ValueKind::Path => "PATH".to_owned(),
+        };
+        match self.arity {
+            Arity::None => String::new(),
+            Arity::One => format!(" <{hint}>"),
+            Arity::Many => format!(" <{hint}>..."),
+        }
+    }
+}
+
+pub struct Rscli {
+    pub program_name: String,
+    /// One line summary of what the tool does, shown in the help and man page.
+    pub about: &'static str,
+    /// The arguments the tool takes, after its name: `[OPTIONS] [FILE]...`.
+    pub synopsis: &'static str,
+    pub program_flags: Vec<Flag>,
+    pub matched_flags: Vec<Flag>,
+    /// Operands as given, which may not be valid UTF-8 when they are paths
+    pub free_args: Vec<OsString>,
+    pub examples: Vec<String>,
+    /// The `Arity::Many` flag that is still collecting bare arguments
+    greedy_flag: Option<&'static str>,
+    /// Flags set by the defaults, whose values the command line replaces
+    defaulted: Vec<&'static str>,
+}
+
+impl Rscli {
+    pub fn new(program_name: String, about: &'static str, synopsis: &'static str, mut program_flags: Vec<Flag>, examples: Vec<String>) -> Self {
+        program_flags.extend(builtin_flags());
+        Rscli {
+            program_name,
+            about,
+            synopsis,
+            program_flags,
+            matched_flags: vec![],
+            free_args: vec![],
+            examples,
+            greedy_flag: None,
+            defaulted: vec![],
+        }
+    }
+
+    /// Parses the command line, after the defaults from the config file and
+    /// `<TOOL>_OPTS` unless `--no-config` is given.
+    pub fn parse_args(&mut self, args: &[OsString]) {
+        let args = &args[1..];
+        let no_config_flag = format!("--{NO_CONFIG_FLAG}");
+        let no_config = args.iter()
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg.to_str() == Some(&no_config_flag));
+
+        if !no_config {
+            // The usage would not help, the mistake is not on the command line
+            let parsed = config::default_args(&self.bin_name(), &self.program_flags).and_then(|all| {
+                all.iter().try_for_each(|defaults| {
+                    self.parse_defaults(&defaults.args)
+                        .map_err(|err| Error::Config(format!("{}: {err}", defaults.source)))
+                })
+            });
+            if let Err(err) = parsed {
+                eprintln!("[ERROR] {err}");
+                exit(EXIT_ERROR);
+            }
+        }
+        if let Err(err) = self.parse_list(args) {
+            self.fail(err);
+        }
+        self.handle_builtin_flags();
+    }
+
+    /// Parses arguments the command line then overrides.
+    fn parse_defaults(&mut self, args: &[String]) -> Result<(), Error> {
+        let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+        self.parse_list(&args)?;
+        // Defaults must not leave a flag collecting the real operands
+        self.greedy_flag = None;
+        self.defaulted = self.matched_flags.iter().map(|f| f.id).collect();
+        Ok(())
+    }
+
+    fn parse_list(&mut self, args: &[OsString]) -> Result<(), Error> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let bytes = arg.as_encoded_bytes();
+            if arg == "--" {
+                // Everything after `--` is an operand, even if it looks like a flag
+                self.free_args.extend(args.by_ref().cloned());
+            } else if bytes.starts_with(b"--") {
+                self.parse_long_flag(arg, &mut args)?;
+            } else if bytes.starts_with(b"-") && arg != "-" {
+                // A lone `-` is an operand, usually standing for stdin
+                self.parse_flags(arg, &mut args)?;
+            } else {
+                self.handle_non_flag(arg)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flags every tool gets for free. They do their job and exit, so the
+    /// tool itself never sees them.
+    fn handle_builtin_flags(&self) {
+        let text = if self.find_user_flag_by_id(HELP_FLAG).is_some() {
+            let mut text = format!("{}\n", self.about).into_bytes();
+            let _ = self.write_usage(&mut text);
+            text
+        } else if self.find_user_flag_by_id(GENERATE_MAN_FLAG).is_some() {
+            help::render_man(self).into_bytes()
+        } else if self.find_user_flag_by_id(GENERATE_MARKDOWN_FLAG).is_some() {
+            help::render_markdown(self).into_bytes()
+        } else if let Some(shell) = self.get_str(GENERATE_COMPLETIONS_FLAG).and_then(Shell::from_name) {
+            completions::generate(self, shell).into_bytes()
+        } else {
+            return;
+        };
+
+        let mut out = Output::new();
+        out.write_bytes(&text);
+        out.flush();
+        exit(EXIT_SUCCESS);
+    }
+
+    /// The program name as the shell knows it, without directories or `.exe`.
+    pub fn bin_name(&self) -> String {
+        Path::new(&self.program_name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.program_name.clone())
+    }
+
+    /// The examples with the program name as typed replaced by `bin_name`,
+    /// so generated documentation does not depend on how the tool was run.
+    pub fn bin_examples(&self) -> Vec<String> {
+        let bin = self.bin_name();
+        self.examples.iter().map(|e| e.replacen(&self.program_name, &bin, 1)).collect()
+    }
+
+    pub fn visible_flags(&self) -> impl Iterator<Item = &Flag> {
+        self.program_flags.iter().filter(|f| !f.hidden)
+    }
+
+    /// Parses a cluster of short flags like `-rn`. A flag that takes a value
+    /// uses the rest of the cluster (`-p/home`, `-C3`) or else the next argument.
+    pub fn parse_flags<'a>(&mut self, arg: &OsStr, rest: &mut impl Iterator<Item = &'a OsString>) -> Result<(), Error> {
+        let bytes = &arg.as_encoded_bytes()[1..]; // Skip the leading '-'
+        // Flags are the valid UTF-8 start of the cluster, the rest can only be a value
+        let shorts = bytes.utf8_chunks().next().map_or("", |chunk| chunk.valid());
+        for (idx, c) in shorts.char_indices() {
+            let Some(flag) = self.find_program_flag_by_short(c).cloned() else {
+                return Err(Error::Usage(format!("Unknown flag: -{c}")));
+            };
+
+            if flag.arity == Arity::None {
+                self.match_flag(flag, &format!("-{c}"), None)?;
+                continue;
+            }
+
+            let attached = 1 + idx + c.len_utf8();
+            let value = if attached == arg.len() {
+                rest.next().cloned()
+            } else {
+                Some(os_tail(arg, attached))
+            };
+            return self.match_flag(flag, &format!("-{c}"), value);
+        }
+        if shorts.len() < bytes.len() {
+            return Err(Error::Usage(format!("Unknown flag: {}", arg.to_string_lossy())));
+        }
+        Ok(())
+    }
+
+    /// Parses `--name`, `--name=value` and `--name value`.
+    pub fn parse_long_flag<'a>(&mut self, arg: &OsStr, rest: &mut impl Iterator<Item = &'a OsString>) -> Result<(), Error> {
+        let bytes = &arg.as_encoded_bytes()[2..]; // Skip the leading '--'
+        let (name, value) = match bytes.iter().position(|&b| b == b'=') {
+            Some(eq) => (&bytes[..eq], Some(os_tail(arg, 2 + eq + 1))),
+            None => (bytes, None),
+        };
+        let name = String::from_utf8_lossy(name);
+
+        let Some(flag) = self.find_program_flag_by_long(&name).cloned() else {
+            return Err(Error::Usage(format!("Unknown flag: --{name}")));
+        };
+
+        let value = match (flag.arity, value) {
+            (Arity::None, Some(_)) => {
+                return Err(Error::Usage(format!("Flag --{name} does not take a value")));
+            }
+            (Arity::None, None) => None,
+            (_, Some(value)) => Some(value),
+            (_, None) => rest.next().cloned(),
+        };
+        self.match_flag(flag, &format!("--{name}"), value)
+    }
+
+    /// Records an occurrence of `flag`, spelled `name` on the command line.
+    /// Repeated flags share a single entry in `matched_flags`.
+    fn match_flag(&mut self, flag: Flag, name: &str, value: Option<OsString>) -> Result<(), Error> {
+        if flag.arity != Arity::None && value.is_none() {
+            return Err(Error::Usage(format!("Flag {name} requires a value")));
+        }
+        if let Some(value) = &value {
+            validate_value(&flag, name, value)?;
+        }
+
+        self.greedy_flag = (flag.arity == Arity::Many).then_some(flag.id);
+        let matched = match self.matched_flags.iter().position(|f| f.id == flag.id) {
+            Some(idx) => &mut self.matched_flags[idx],
+            None => {
+                self.matched_flags.push(flag);
+                self.matched_flags.last_mut().unwrap()
+            }
+        };
+        // The first time the command line sets a flag, its values replace the defaults
+        if let Some(idx) = self.defaulted.iter().position(|&id| id == matched.id) {
+            self.defaulted.swap_remove(idx);
+            matched.values.clear();
+        }
+        matched.values.extend(value);
+        Ok(())
+    }
+
+    /// Bare arguments only become flag values while an `Arity::Many` flag is
+    /// collecting them, everything else is an operand.
+    pub fn handle_non_flag(&mut self, arg: &OsStr) -> Result<(), Error> {
+        let greedy = self.greedy_flag.and_then(|id| self.matched_flags.iter().position(|f| f.id == id));
+        if let Some(idx) = greedy {
+            let flag = &mut self.matched_flags[idx];
+            validate_value(flag, &flag.names(), arg)?;
+            flag.values.push(arg.to_owned());
+        } else {
+            self.free_args.push(arg.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Reports a command line error and exits before the tool does any work.
+    pub fn fail(&self, err: Error) -> ! {
+        eprintln!("[ERROR] {err}");
+        self.usage();
+    }
+    
+
+    pub fn find_program_flag_by_id(&self, id: &str) -> Option<&Flag> {
+        self.program_flags.iter().find(|f| f.id == id)
+    }
+
+    pub fn find_program_flag_by_short(&self, short: char) -> Option<&Flag> {
+        self.program_flags.iter().find(|f| f.short() == Some(short))
+    }
+
+    pub fn find_program_flag_by_long(&self, long: &str) -> Option<&Flag> {
+        self.program_flags.iter().find(|f| f.long == Some(long))
+    }
+    
+    pub fn find_user_flag_by_id(&self, id: &str) -> Option<&Flag> {
+        self.matched_flags.iter().find(|f| f.id == id)
+    }
+
+    /// The last value given to the flag, so a later occurrence overrides an
+    /// earlier one. Only `ValueKind::Path` values can fail to be UTF-8.
+    pub fn get_str(&self, id: &str) -> Option<&str> {
+        self.find_user_flag_by_id(id)?.values.last()?.to_str()
+    }
+
+    /// Every value given to the flag, in order.
+    pub fn get_strs(&self, id: &str) -> Vec<&str> {
+        self.find_user_flag_by_id(id)
+            .map(|flag| flag.values.iter().filter_map(|value| value.to_str()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_usize(&self, id: &str) -> Option<usize> {
+        parse_integer(self.get_str(id)?).ok()
+    }
+
+    pub fn get_size(&self, id: &str) -> Option<u64> {
+        parse_size(self.get_str(id)?).ok()
+    }
+
+    pub fn get_duration(&self, id: &str) -> Option<Duration> {
+        parse_duration(self.get_str(id)?).ok()
+    }
+
+    pub fn get_path(&self, id: &str) -> Option<PathBuf> {
+        self.find_user_flag_by_id(id)?.values.last().map(PathBuf::from)
+    }
+
+    pub fn get_paths(&self, id: &str) -> Vec<PathBuf> {
+        self.find_user_flag_by_id(id)
+            .map(|flag| flag.values.iter().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn usage(&self) -> ! {
+        let _ = self.write_usage(&mut io::stderr());
+        exit(EXIT_ERROR);
+    }
+
+    pub fn write_usage(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out)?;
+        writeln!(out, "USAGE: {} {}", self.program_name, self.synopsis)?;
+        if self.visible_flags().next().is_some() {
+            writeln!(out, "OPTIONS:")?;
+        }
+        let names: Vec<String> = self.visible_flags()
+            .map(|f| format!("{}{}", f.names(), f.value_hint()))
+            .collect();
+        let width = names.iter().map(String::len).max().unwrap_or(0);
+        for (flag, names) in self.visible_flags().zip(names) {
+            writeln!(out, "    {names:<width$}    {}", flag.description)?;
+        }
+
+        writeln!(out, "EXAMPLES:")?;
+        for example in &self.examples {
+            writeln!(out, "{example}")?;
+        }
+        writeln!(out)
+    }
+}
+
+fn validate_value(flag: &Flag, name: &str, value: &OsStr) -> Result<(), Error> {
+    let invalid = |reason| Error::Usage(format!("Invalid value '{}' for {name}: {reason}", value.to_string_lossy()));
+    match value.to_str() {
+        Some(text) => flag.kind.validate(text).map_err(invalid),
+        // Paths are whatever the OS allows, other values are read as text
+        None if flag.kind == ValueKind::Path => Ok(()),
+        None => Err(invalid("not valid UTF-8".to_owned())),
+    }
+}
+
+/// The part of `arg` from byte `start` on, where `start` is right after a
+/// flag name or `=`.
+fn os_tail(arg: &OsStr, start: usize) -> OsString {
+    let bytes = &arg.as_encoded_bytes()[start..];
+    // SAFETY: the split comes right after valid UTF-8, which keeps both
+    // sides valid encoded bytes
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }.to_owned()
+}
+
+/// Whether `flag` is one of the flags every tool gets from `builtin_flags`.
+pub(crate) fn is_builtin(flag: &Flag) -> bool {
+    builtin_flags().iter().any(|builtin| builtin.id == flag.id)
+}
+
+fn builtin_flags() -> Vec<Flag> {
+    vec![
+        Flag {
+            id: HELP_FLAG,
+            long: Some(HELP_LONG),
+            description: HELP_DESC,
+            ..Default::default()
+        },
+        Flag {
+            id: NO_CONFIG_FLAG,
+            long: Some(NO_CONFIG_FLAG),
+            description: NO_CONFIG_DESC,
+            ..Default::default()
+        },
+        Flag {
+            id: GENERATE_COMPLETIONS_FLAG,
+            long: Some(GENERATE_COMPLETIONS_FLAG),
+            description: GENERATE_COMPLETIONS_DESC,
+            arity: Arity::One,
+            kind: ValueKind::Choice(completions::SHELLS),
+            hidden: true,
+            ..Default::default()
+        },
+        Flag {
+            id: GENERATE_MAN_FLAG,
+            long: Some(GENERATE_MAN_FLAG),
+            description: GENERATE_MAN_DESC,
+            hidden: true,
+            ..Default::default()
+        },
+        Flag {
+            id: GENERATE_MARKDOWN_FLAG,
+            long: Some(GENERATE_MARKDOWN_FLAG),
+            description: GENERATE_MARKDOWN_DESC,
+            hidden: true,
+            ..Default::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> Rscli {
+        let flags = vec![
+            Flag { id: "r", long: Some("recursive"), ..Default::default() },
+            Flag { id: "n", long: Some("line-number"), ..Default::default() },
+            Flag { id: "p", long: Some("path"), arity: Arity::One, kind: ValueKind::Path, ..Default::default() },
+            Flag { id: "C", long: Some("context"), arity: Arity::One, kind: ValueKind::Integer, ..Default::default() },
+            Flag { id: "e", long: Some("exclude"), arity: Arity::Many, ..Default::default() },
+        ];
+        Rscli::new("tool".to_owned(), "", "[OPTIONS] [ARG]...", flags, vec![])
+    }
+
+    fn parse(args: &[&str]) -> Result<Rscli, Error> {
+        let mut cli = cli();
+        let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+        cli.parse_list(&args)?;
+        Ok(cli)
+    }
+
+    #[test]
+    fn short_flags_cluster() {
+        let cli = parse(&["-rn", "x"]).unwrap();
+        assert!(cli.find_user_flag_by_id("r").is_some());
+        assert!(cli.find_user_flag_by_id("n").is_some());
+        assert_eq!(cli.free_args, ["x"]);
+    }
+
+    #[test]
+    fn short_value_attached_or_next() {
+        let cli = parse(&["-p/home", "-C3"]).unwrap();
+        assert_eq!(cli.get_strs("p"), ["/home"]);
+        assert_eq!(cli.get_usize("C"), Some(3));
+
+        let cli = parse(&["-rp", "/home", "x"]).unwrap();
+        assert!(cli.find_user_flag_by_id("r").is_some());
+        assert_eq!(cli.get_strs("p"), ["/home"]);
+        assert_eq!(cli.free_args, ["x"]);
+    }
+
+    #[test]
+    fn long_value_with_equals_or_next() {
+        let cli = parse(&["--path=/home", "--context", "2"]).unwrap();
+        assert_eq!(cli.get_strs("p"), ["/home"]);
+        assert_eq!(cli.get_usize("C"), Some(2));
+
+        let cli = parse(&["--exclude="]).unwrap();
+        assert_eq!(cli.get_strs("e"), [""]);
+    }
+
+    #[test]
+    fn switch_rejects_value() {
+        assert!(parse(&["--recursive="]).is_err());
+        assert!(parse(&["--recursive=yes"]).is_err());
+    }
+
+    #[test]
+    fn missing_value() {
+        assert!(parse(&["-p"]).is_err());
+        assert!(parse(&["--path"]).is_err());
+    }
+
+    #[test]
+    fn unknown_flag() {
+        assert!(parse(&["-z"]).is_err());
+        assert!(parse(&["--zzz"]).is_err());
+    }
+
+    #[test]
+    fn operands_after_double_dash() {
+        let cli = parse(&["-n", "--", "-r", "--path", "-"]).unwrap();
+        assert!(cli.find_user_flag_by_id("r").is_none());
+        assert_eq!(cli.free_args, ["-r", "--path", "-"]);
+    }
+
+    #[test]
+    fn lone_dash_is_operand() {
+        let cli = parse(&["-", "-n"]).unwrap();
+        assert_eq!(cli.free_args, ["-"]);
+    }
+
+    #[test]
+    fn many_collects_until_next_flag() {
+        let cli = parse(&["-e", "a", "b", "-r", "c", "-e", "d"]).unwrap();
+        assert_eq!(cli.get_strs("e"), ["a", "b", "d"]);
+        assert_eq!(cli.free_args, ["c"]);
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let cli = parse(&["-p", "a", "--path=b"]).unwrap();
+        assert_eq!(cli.get_str("p"), Some("b"));
+    }
+
+    #[test]
+    fn invalid_value() {
+        assert!(parse(&["-Cx"]).is_err());
+        assert!(parse(&["--context=-1"]).is_err());
+        assert!(parse(&["--path="]).is_err());
+    }
+
+    #[test]
+    fn choice() {
+        let kind = ValueKind::Choice(&["auto", "always", "never"]);
+        assert!(kind.validate("always").is_ok());
+        assert!(kind.validate("sometimes").is_err());
+        assert!(kind.validate("").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("10kib"), Ok(10 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+        assert!(parse_duration("5M").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+
+    #[test]
+    fn command_line_replaces_defaults() {
+        let mut cli = cli();
+        let strings = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        cli.parse_defaults(&strings(&["-n", "-p", "m.txt", "-e", "a"])).unwrap();
+        let args: Vec<OsString> = ["x", "-p", "n.txt", "--path", "o.txt"].iter().map(OsString::from).collect();
+        cli.parse_list(&args).unwrap();
+        assert_eq!(cli.get_strs("p"), ["n.txt", "o.txt"]);
+        assert_eq!(cli.get_strs("e"), ["a"]);
+        assert!(cli.find_user_flag_by_id("n").is_some());
+        // A trailing Many flag in the defaults does not take the operands
+        assert_eq!(cli.free_args, ["x"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_pass_through() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let bad = OsStr::from_bytes(b"bad\xffname");
+        let args = [OsString::from("-p"), bad.to_owned(), OsString::from("-rp"), bad.to_owned(), bad.to_owned()];
+        let mut clustered = cli();
+        clustered.parse_list(&args).unwrap();
+        assert_eq!(clustered.get_path("p"), Some(PathBuf::from(bad)));
+        assert_eq!(clustered.free_args, [bad]);
+
+        let mut attached = b"--path=".to_vec();
+        attached.extend_from_slice(bad.as_bytes());
+        let mut long = cli();
+        long.parse_list(&[OsStr::from_bytes(&attached).to_owned()]).unwrap();
+        assert_eq!(long.get_path("p"), Some(PathBuf::from(bad)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_text_rejected() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let bad = OsStr::from_bytes(b"\xff");
+        assert!(cli().parse_list(&[OsString::from("-C"), bad.to_owned()]).is_err());
+        assert!(cli().parse_list(&[OsString::from("-e"), bad.to_owned()]).is_err());
+        assert!(cli().parse_list(&[OsStr::from_bytes(b"-r\xff").to_owned()]).is_err());
+    }
+}